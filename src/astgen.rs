@@ -1,19 +1,46 @@
 use std::collections::HashMap;
+use std::fmt;
 
-use crate::tokenizer::{Token, WrappedToken};
+use crate::diagnostic::{closest_match, Diagnostic};
+use crate::expression::ExpressionParser;
+use crate::tokenizer::{Span, Token, WrappedToken};
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Value {
-    IntegerLiteral(u32),
+    IntegerLiteral(i64),
+    FloatLiteral(f64),
     StringLiteral(String),
     BoolLiteral(bool),
-    Variable(String),
+    // nodes that can fail at runtime carry their span, so errors point into the expression
+    Variable(String, Span),
     Array(Vec<Value>),
     Return,
     Null,
+    Unary {
+        operator: Operator,
+        value: Box<Value>,
+        span: Span,
+    },
+    // the accessor `.` has a Null right hand side
     Expression {
-        values: Vec<Value>,
-        operators: Vec<Operator>,
+        operator: Operator,
+        lhs: Box<Value>,
+        rhs: Box<Value>,
+        span: Span,
+    },
+    // subroutine called inside an expression, the index is filled in once every sub is known
+    Call {
+        name: String,
+        arguments: Vec<Value>,
+        subroutine_idx: Option<usize>,
+        span: Span,
+    },
+    // `receiver.name(arguments)`, looked up in the method table when run
+    MethodCall {
+        name: String,
+        receiver: Box<Value>,
+        arguments: Vec<Value>,
+        span: Span,
     },
 }
 
@@ -21,6 +48,13 @@ pub enum Value {
 pub enum Operator {
     Add,
     Sub,
+    Mul,
+    Div,
+    Mod,
+    Negate,
+    Not,
+    And,
+    Or,
     Equals,
     NotEquals,
     MoreThan,
@@ -29,8 +63,30 @@ pub enum Operator {
     LessThanOrEquals,
     ArrayAccess,
     LenAccess,
-    PopAccess,
-    PopFrontAccess,
+}
+
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Operator::Add => write!(f, "`+`"),
+            Operator::Sub => write!(f, "`-`"),
+            Operator::Mul => write!(f, "`*`"),
+            Operator::Div => write!(f, "`/`"),
+            Operator::Mod => write!(f, "`%`"),
+            Operator::Negate => write!(f, "unary `-`"),
+            Operator::Not => write!(f, "`!`"),
+            Operator::And => write!(f, "`&&`"),
+            Operator::Or => write!(f, "`||`"),
+            Operator::Equals => write!(f, "`==`"),
+            Operator::NotEquals => write!(f, "`!=`"),
+            Operator::MoreThan => write!(f, "`>`"),
+            Operator::LessThan => write!(f, "`<`"),
+            Operator::MoreThanOrEquals => write!(f, "`>=`"),
+            Operator::LessThanOrEquals => write!(f, "`<=`"),
+            Operator::ArrayAccess => write!(f, "`|index|`"),
+            Operator::LenAccess => write!(f, "`.`"),
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -39,14 +95,24 @@ pub enum Statement {
     Block,
     BlockEnd,
     Alloc,
+    // like alloc, but hides a variable of the same name until the scope ends, for loop variables
+    Shadow,
     Set,
+    // `arr|i| += value`, the element is found once and updated in place
+    Update(Operator),
     DebugPrintCall,
     ReadLineCall,
-    EOF,
+    Global,
+    // a method call on its own, the result is dropped
+    Evaluate,
+    Eof,
     // conditions
-    If(Operator),
+    If,
     Else,
-    While(Operator),
+    While,
+    // index of the while being left or restarted
+    Break(usize),
+    Continue(usize),
     // subroutines
     SubroutineCall(Option<usize>),
     SubroutineReturn,
@@ -62,38 +128,39 @@ pub struct ASTToken {
     // args for nested code blocks (if/else)
     pub body_idx: Option<usize>,
     pub body_extent: Option<usize>,
-    pub src_line: usize,
+    // source extent of the whole statement
+    pub span: Span,
 }
 
 impl ASTToken {
-    pub fn empty(src_line: usize) -> Self {
+    pub fn empty(span: Span) -> Self {
         Self {
-            t_type: Statement::Empty, arg1: None, arg2: None, body_idx: None, body_extent: None, src_line
+            t_type: Statement::Empty, arg1: None, arg2: None, body_idx: None, body_extent: None, span
         }
     }
-    pub fn of_type(t_type: Statement, src_line: usize) -> Self {
+    pub fn of_type(t_type: Statement, span: Span) -> Self {
         Self {
-            t_type, arg1: None, arg2: None, body_idx: None, body_extent: None, src_line
+            t_type, arg1: None, arg2: None, body_idx: None, body_extent: None, span
         }
     }
-    pub fn with_args(t_type: Statement, arg1: Value, arg2: Option<Value>, src_line: usize) -> Self {
+    pub fn with_args(t_type: Statement, arg1: Value, arg2: Option<Value>, span: Span) -> Self {
         Self {
-            t_type, arg1: Some(arg1), arg2: arg2, body_idx: None, body_extent: None, src_line
+            t_type, arg1: Some(arg1), arg2, body_idx: None, body_extent: None, span
         }
     }
-    pub fn with_body(t_type: Statement, body_idx: usize, src_line: usize) -> Self {
+    pub fn with_body(t_type: Statement, body_idx: usize, span: Span) -> Self {
         Self {
-            t_type, arg1: None, arg2: None, body_idx: Some(body_idx), body_extent: None, src_line
+            t_type, arg1: None, arg2: None, body_idx: Some(body_idx), body_extent: None, span
         }
     }
-    pub fn with_args_and_body(t_type: Statement, arg1: Value, arg2: Option<Value>, body_idx: usize, src_line: usize) -> Self {
+    pub fn with_args_and_body(t_type: Statement, arg1: Value, arg2: Option<Value>, body_idx: usize, span: Span) -> Self {
         Self {
-            t_type, arg1: Some(arg1), arg2: arg2, body_idx: Some(body_idx), body_extent: None, src_line
+            t_type, arg1: Some(arg1), arg2, body_idx: Some(body_idx), body_extent: None, span
         }
     }
-    pub fn new_scope(body_idx: usize, src_line: usize) -> Self {
+    pub fn new_scope(body_idx: usize, span: Span) -> Self {
         Self {
-            t_type: Statement::Block, arg1: None, arg2: None, body_idx: Some(body_idx), body_extent: None, src_line
+            t_type: Statement::Block, arg1: None, arg2: None, body_idx: Some(body_idx), body_extent: None, span
         }
     }
 }
@@ -103,6 +170,12 @@ pub struct ASTGenerator {
     current_token_idx: usize,
     pub generated_ast: Vec<ASTToken>,
     scope_open_idxs: Vec<usize>,
+    // label of the loop about to be parsed
+    loop_label: Option<String>,
+    // whiles that for loops were lowered onto, their closing also closes the scope around them
+    lowered_for_loops: Vec<usize>,
+    // ast index right after the block of an if or else if, the only place an else may go
+    else_allowed_at: Option<usize>,
     // subroutine name, subroutine index
     subroutine_table: HashMap<String, usize>,
     // subroutine name to call, vec of indexes of calls
@@ -116,332 +189,344 @@ impl ASTGenerator {
             current_token_idx: 0,
             generated_ast: vec![],
             scope_open_idxs: vec![],
+            loop_label: None,
+            lowered_for_loops: vec![],
+            else_allowed_at: None,
             subroutine_table: HashMap::new(),
             subroutine_calls: HashMap::new(),
         }
     }
-    fn advance_and_get_token(&mut self) -> &WrappedToken {
+    fn advance_and_get_token(&mut self) -> Result<&WrappedToken, Diagnostic> {
+        if self.current_token_idx + 1 >= self.tokens.len() {
+            return Err(self.unexpected_end_of_file());
+        }
+
         self.current_token_idx += 1;
-        &self.tokens[self.current_token_idx]
+        Ok(&self.tokens[self.current_token_idx])
     }
     fn advance_token(&mut self) {
         self.current_token_idx += 1;
     }
-    fn get_token(&mut self) -> &WrappedToken {
+    fn get_token(&self) -> &WrappedToken {
         &self.tokens[self.current_token_idx]
     }
     fn peek_next_token(&self) -> Option<&WrappedToken> {
-        if self.current_token_idx < self.tokens.len() {
-            Some(&self.tokens[self.current_token_idx + 1])
-        } else {
-            None
+        self.tokens.get(self.current_token_idx + 1)
+    }
+    fn statement_span(&self, start_token: &WrappedToken) -> Span {
+        // from the statement's first token up to the current token
+        start_token.span.to(self.tokens[self.current_token_idx].span)
+    }
+    fn unexpected_end_of_file(&self) -> Diagnostic {
+        Diagnostic::parser(
+            "Unexpected end of file".to_string(),
+            self.tokens.last().map_or(Span::default(), |token| token.span),
+        ).with_label("statement is incomplete")
+    }
+    fn expect_next_token(&self, expected: Token) -> Result<(), Diagnostic> {
+        let diagnostic = match self.peek_next_token() {
+            Some(token) if token.token == expected => return Ok(()),
+            Some(token) if token.token != Token::Eof => Diagnostic::parser(
+                format!("Expected {}, found {}", expected, token.token),
+                token.span,
+            ).with_label(&format!("expected {} here", expected)),
+            _ => Diagnostic::parser(
+                format!("Expected {} before end of file", expected),
+                self.tokens[self.current_token_idx].span,
+            ).with_label(&format!("expected {} after this", expected)),
+        };
+
+        match expected {
+            Token::LineEnd => Err(diagnostic.with_help("statements end with `;`")),
+            Token::ScopeOpen => Err(diagnostic.with_help("the body of this statement must be a `{ }` block")),
+            _ => Err(diagnostic),
         }
     }
-    fn resolve_value_from_token(token: &WrappedToken) -> Value {
+    fn resolve_variable_write_like_token(token: &WrappedToken) -> Result<Value, Diagnostic> {
         match &token.token {
-            Token::IntegerLiteral(value) => Value::IntegerLiteral(value.to_owned()),
-            Token::StringLiteral(value) => Value::StringLiteral(value.to_owned()),
-            Token::BoolTrue => Value::BoolLiteral(true),
-            Token::BoolFalse => Value::BoolLiteral(false),
-            Token::Variable(value) => Value::Variable(value.to_owned()),
-            _ => panic!("LINE {} | {:?} passed as value for value-like token!", token.src_line, token),
+            Token::Variable(value) => Ok(Value::Variable(value.to_owned(), token.span)),
+            _ => Err(
+                Diagnostic::parser(format!("Expected a variable to assign to, found {}", token.token), token.span)
+                    .with_label("not a variable name")
+            ),
         }
     }
-    fn resolve_variable_write_like_token(token: &WrappedToken) -> Value {
-        match &token.token {
-            Token::Variable(value) => Value::Variable(value.to_owned()),
-            _ => panic!("LINE {} | {:?} passed as value for variable write-like token!", token.src_line, token),
+    fn advance_and_resolve_assign_target(&mut self, tokens: Vec<WrappedToken>) -> Result<Value, Diagnostic> {
+        let (target, span) = self.advance_and_parse_statement_head(tokens)?;
+
+        ASTGenerator::check_assign_target(&target, span)?;
+        Ok(target)
+    }
+    fn advance_and_parse_statement_head(&mut self, mut tokens: Vec<WrappedToken>) -> Result<(Value, Span), Diagnostic> {
+        // everything up to the assign operator, so `grid|y||x|` can be written to
+        while let Some(next_token) = self.peek_next_token() {
+            if ASTGenerator::token_is_assign_like(next_token)
+            || ASTGenerator::token_is_assign_op_like(next_token)
+            || ASTGenerator::token_is_scope_like(next_token)
+            || ASTGenerator::token_is_line_end(next_token)
+            || next_token.token == Token::Eof {
+                break;
+            }
+
+            tokens.push(self.advance_and_get_token()?.to_owned());
+        }
+
+        if tokens.is_empty() {
+            let token = self.peek_next_token().unwrap_or(self.get_token());
+            return Err(
+                Diagnostic::parser(format!("Expected a variable to assign to, found {}", token.token), token.span)
+                    .with_label("not a variable name")
+            );
+        }
+
+        Ok((ExpressionParser::parse(&tokens)?, tokens[0].span.to(tokens[tokens.len() - 1].span)))
+    }
+    fn check_assign_target(target: &Value, span: Span) -> Result<(), Diagnostic> {
+        if !ASTGenerator::is_assign_target(target) {
+            return Err(
+                Diagnostic::parser("Cannot assign to this expression".to_string(), span)
+                    .with_label("not a variable or an element of one")
+            );
         }
+
+        Ok(())
     }
-    fn resolve_variable_name_like_token(token: &WrappedToken) -> Option<String> {
-        match &token.token {
-            Token::Variable(value) => Some(value.to_owned()),
-            _ => panic!("LINE {} | {:?} passed as value for variable name-like token!", token.src_line, token),
+    fn is_assign_target(value: &Value) -> bool {
+        match value {
+            Value::Variable(..) => true,
+            Value::Expression { operator: Operator::ArrayAccess, lhs, .. } => ASTGenerator::is_assign_target(lhs),
+            _ => false,
         }
     }
-    fn resolve_comparison_like_token(token: &WrappedToken) -> Operator {
+    fn resolve_variable_name_like_token(token: &WrappedToken) -> Result<String, Diagnostic> {
         match &token.token {
-            Token::Equals => Operator::Equals,
-            Token::NotEquals => Operator::NotEquals,
-            Token::MoreThan => Operator::MoreThan,
-            Token::LessThan => Operator::LessThan,
-            Token::MoreThanOrEquals => Operator::MoreThanOrEquals,
-            Token::LessThanOrEquals => Operator::LessThanOrEquals,
-            _ => panic!("LINE {} | {:?} passed as value for comparison-like token!", token.src_line, token),
+            Token::Variable(value) => Ok(value.to_owned()),
+            _ => Err(
+                Diagnostic::parser(format!("Expected a name, found {}", token.token), token.span)
+                    .with_label("not a name")
+            ),
         }
     }
-    fn advance_and_gather_tokens_for_value(&mut self) -> Vec<WrappedToken> {
+    fn advance_and_gather_tokens_for_value(&mut self) -> Result<Vec<WrappedToken>, Diagnostic> {
         let mut tokens: Vec<WrappedToken> = vec![];
 
-        while
-            !ASTGenerator::token_is_scope_like(self.peek_next_token().unwrap())
-            && !ASTGenerator::token_is_line_end(self.peek_next_token().unwrap())
-        {
-            tokens.push(self.advance_and_get_token().to_owned());
-        }
-
-        tokens
-    }
-    fn resolve_any_value(tokens: Vec<WrappedToken>) -> Value {
-        if tokens.len() == 1 {
-            // single literal
-            return ASTGenerator::resolve_value_from_token(tokens.get(0).unwrap());
-        } else if tokens.len() > 1 {
-            let mut array_scratch: Vec<Vec<WrappedToken>> = vec![vec![]];
-            let mut token_idx = 0;
-            let mut value_tokens: Vec<Value> = vec![];
-            let mut operator_tokens: Vec<Operator> = vec![];
-
-            while token_idx < tokens.len() {
-                if tokens[token_idx].token == Token::ArrayOpen {
-                    // handle array
-                    token_idx += 1;
-
-                    while tokens[token_idx].token != Token::ArrayClose {
-                        let this_token = tokens[token_idx].to_owned();
-
-                        if ASTGenerator::token_is_assign_like(&this_token)
-                        || ASTGenerator::token_is_assign_op_like(&this_token)
-                        || ASTGenerator::token_is_comparison_like(&this_token)
-                        || ASTGenerator::token_is_line_end(&this_token)
-                        || ASTGenerator::token_is_scope_like(&this_token) {
-                            panic!("LINE {} | Array incomplete!", this_token.src_line);
-                        }
+        while let Some(next_token) = self.peek_next_token() {
+            if ASTGenerator::token_is_scope_like(next_token)
+            || ASTGenerator::token_is_line_end(next_token)
+            || next_token.token == Token::Eof {
+                break;
+            }
 
-                        match tokens[token_idx].token {
-                            Token::ParensOpen => {
-                                // coalesce tokens in ()
-                                let mut parens_tokens: Vec<WrappedToken> = vec![];
-                                let mut parens_deep: usize = 0;
-                                // skip opening parens
-                                token_idx += 1;
-
-                                while tokens[token_idx].token != Token::ParensClose || parens_deep > 0 {
-                                    if tokens[token_idx].token == Token::ParensOpen {
-                                        parens_deep += 1;
-                                    } else if tokens[token_idx].token == Token::ParensClose {
-                                        parens_deep -= 1;
-                                    }
-
-                                    parens_tokens.push(tokens[token_idx].to_owned());
-                                    token_idx += 1;
-                                }
-
-                                array_scratch.last_mut().unwrap().extend(parens_tokens);
-                            }
-                            Token::ArrayOpen => {
-                                // coalesce tokens in []
-                                let mut arr_tokens: Vec<WrappedToken> = vec![];
-                                let mut arrs_deep: usize = 0;
-                                // skip opening bracket
-                                token_idx += 1;
-                                // this is needed for resolve_any_value to not have a hissy fit
-                                arr_tokens.push(WrappedToken::from(Token::ArrayOpen));
-
-                                while tokens[token_idx].token != Token::ArrayClose || arrs_deep > 0 {
-                                    if tokens[token_idx].token == Token::ArrayOpen {
-                                        arrs_deep += 1;
-                                    } else if tokens[token_idx].token == Token::ArrayClose {
-                                        arrs_deep -= 1;
-                                    }
-
-                                    arr_tokens.push(tokens[token_idx].to_owned());
-                                    token_idx += 1;
-                                }
-
-                                // this is also needed for resolve_any_value to not have a hissy fit
-                                arr_tokens.push(WrappedToken::from(Token::ArrayClose));
-                                array_scratch.last_mut().unwrap().extend(arr_tokens);
-                            }
-                            Token::Comma => {
-                                // make new token vector
-                                array_scratch.push(vec![]);
-                            }
-                            _ => {
-                                array_scratch.last_mut().unwrap().push(this_token);
-                            }
-                        }
+            tokens.push(self.advance_and_get_token()?.to_owned());
+        }
 
-                        token_idx += 1;
-                    }
+        if tokens.is_empty() {
+            let span = self.get_token().span;
+            return Err(Diagnostic::parser("Expected a value".to_string(), span).with_label("expected a value after this"));
+        }
 
-                    let mut array_token_values: Vec<Value> = vec![];
+        Ok(tokens)
+    }
+    fn advance_and_gather_parenthesized_tokens(&mut self) -> Result<Vec<WrappedToken>, Diagnostic> {
+        // skip the opening (, nested parens are kept for the expression parser
+        let open_token = self.advance_and_get_token()?.to_owned();
+        let mut tokens: Vec<WrappedToken> = vec![];
+        let mut depth = 0;
+
+        loop {
+            match self.peek_next_token() {
+                Some(token) if token.token == Token::ParensClose && depth == 0 => break,
+                Some(token) if token.token == Token::ParensOpen => depth += 1,
+                Some(token) if token.token == Token::ParensClose => depth -= 1,
+                Some(token) if token.token != Token::LineEnd && token.token != Token::ScopeOpen && token.token != Token::Eof => {}
+                _ => return Err(
+                    Diagnostic::parser("Missing closing `)`".to_string(), open_token.span).with_label("opened here")
+                ),
+            }
 
-                    for token_vec in &array_scratch {
-                        if token_vec.len() > 0 {
-                            array_token_values.push(ASTGenerator::resolve_any_value(token_vec.to_owned()));
-                        }
-                    }
+            tokens.push(self.advance_and_get_token()?.to_owned());
+        }
 
-                    value_tokens.push(Value::Array(array_token_values));
-                    array_scratch.clear();
-                } else if ASTGenerator::token_is_comparison_like(&tokens[token_idx]) {
-                    // add new operator and move temp tokens to list of token lists
-                    operator_tokens.push(ASTGenerator::resolve_comparison_like_token(&tokens[token_idx]));
-                } else {
-                    let this_token = tokens[token_idx].to_owned();
-
-                    match &this_token.token {
-                        // values
-                        Token::IntegerLiteral(_) => value_tokens.push(ASTGenerator::resolve_value_from_token(&this_token)),
-                        Token::StringLiteral(_) => value_tokens.push(ASTGenerator::resolve_value_from_token(&this_token)),
-                        Token::BoolTrue => value_tokens.push(ASTGenerator::resolve_value_from_token(&this_token)),
-                        Token::BoolFalse => value_tokens.push(ASTGenerator::resolve_value_from_token(&this_token)),
-                        Token::Variable(_) => value_tokens.push(ASTGenerator::resolve_value_from_token(&this_token)),
-                        // operators
-                        Token::Plus => operator_tokens.push(Operator::Add),
-                        Token::Minus => operator_tokens.push(Operator::Sub),
-                        Token::ArrayAccess => {
-                            // accessing array from previous value token, coalesce
-                            let array_value = value_tokens.pop().unwrap();
-                            let mut access_tokens: Vec<WrappedToken> = vec![];
-                            // skip opening array access
-                            token_idx += 1;
-
-                            while tokens[token_idx].token != Token::ArrayAccess {
-                                access_tokens.push(tokens[token_idx].to_owned());
-                                token_idx += 1;
-                            }
+        self.advance_token(); // skip closing )
+        Ok(tokens)
+    }
+    fn advance_and_gather_parameters(&mut self) -> Result<Vec<Value>, Diagnostic> {
+        let mut parameters: Vec<Value> = vec![];
 
-                            value_tokens.push(
-                                Value::Expression {
-                                    values: vec![
-                                        array_value,
-                                        ASTGenerator::resolve_any_value(access_tokens)
-                                    ],
-                                    operators: vec![Operator::ArrayAccess],
-                                }
-                            );
-                        },
-                        Token::LenAccess => {
-                            // accessing length of previous value token, coalesce
-                            let value = value_tokens.pop().unwrap();
-
-                            value_tokens.push(
-                                Value::Expression {
-                                    values: vec![
-                                        value,
-                                        Value::Null
-                                    ],
-                                    operators: vec![Operator::LenAccess],
-                                }
-                            );
-                        },
-                        Token::PopAccess => {
-                            // popping previous value token, coalesce
-                            let value = value_tokens.pop().unwrap();
-
-                            value_tokens.push(
-                                Value::Expression {
-                                    values: vec![
-                                        value,
-                                        Value::Null
-                                    ],
-                                    operators: vec![Operator::PopAccess],
-                                }
-                            );
-                        },
-                        Token::PopFrontAccess => {
-                            // popping previous value token, coalesce
-                            let value = value_tokens.pop().unwrap();
-
-                            value_tokens.push(
-                                Value::Expression {
-                                    values: vec![
-                                        value,
-                                        Value::Null
-                                    ],
-                                    operators: vec![Operator::PopFrontAccess],
-                                }
-                            );
-                        },
-                        Token::ParensOpen => {
-                            // coalesce tokens in ()
-                            let mut parens_tokens: Vec<WrappedToken> = vec![];
-                            let mut parens_deep: usize = 0;
-                            // skip opening parens
-                            token_idx += 1;
-
-                            while tokens[token_idx].token != Token::ParensClose || parens_deep > 0 {
-                                if tokens[token_idx].token == Token::ParensOpen {
-                                    parens_deep += 1;
-                                } else if tokens[token_idx].token == Token::ParensClose {
-                                    parens_deep -= 1;
-                                }
-
-                                parens_tokens.push(tokens[token_idx].to_owned());
-                                token_idx += 1;
-                            }
+        self.advance_token(); // skip opening (
 
-                            value_tokens.push(ASTGenerator::resolve_any_value(parens_tokens));
-                        }
-                        _ => panic!("LINE {} | {:?} passed as value for variable read token!", this_token.src_line, this_token),
+        loop {
+            let token = self.advance_and_get_token()?.to_owned();
+
+            match &token.token {
+                Token::ParensClose if parameters.is_empty() => break,
+                Token::Variable(name) => {
+                    if parameters.iter().any(|parameter| matches!(parameter, Value::Variable(existing, _) if existing == name)) {
+                        return Err(Diagnostic::parser(format!("Duplicate parameter '{}'", name), token.span)
+                            .with_label("already declared in this parameter list"));
                     }
+
+                    parameters.push(Value::Variable(name.to_owned(), token.span));
+                }
+                _ => return Err(Diagnostic::parser(format!("Expected a parameter name, found {}", token.token), token.span)
+                    .with_label("not a name")),
+            }
+
+            let separator = self.advance_and_get_token()?.to_owned();
+
+            match separator.token {
+                Token::Comma => {}
+                Token::ParensClose => break,
+                _ => return Err(Diagnostic::parser(format!("Expected `,` or `)`, found {}", separator.token), separator.span)
+                    .with_label("expected `,` or `)` here")),
+            }
+        }
+
+        Ok(parameters)
+    }
+    fn resolve_any_value(tokens: Vec<WrappedToken>) -> Result<Value, Diagnostic> {
+        ExpressionParser::parse(&tokens)
+    }
+    fn find_enclosing_loop(&self, label: &Option<String>, span: Span) -> Result<usize, Diagnostic> {
+        for scope_open_idx in self.scope_open_idxs.iter().rev() {
+            let owner = &self.generated_ast[scope_open_idx - 1];
+
+            match owner.t_type {
+                Statement::While if label.is_none() || owner.arg2 == label.to_owned().map(Value::StringLiteral) => {
+                    return Ok(scope_open_idx - 1);
                 }
+                // loops around a subroutine definition cannot be left from inside it
+                Statement::SubroutineDefine => break,
+                _ => {}
+            }
+        }
+
+        match label {
+            Some(label) => Err(Diagnostic::parser(format!("No enclosing loop labeled '{}'", label), span)
+                .with_label("unknown label")),
+            None => Err(Diagnostic::parser("Not inside a loop".to_string(), span)
+                .with_label("can only be used inside a loop")),
+        }
+    }
+    // None when there is no such subroutine, those are reported together once every call is seen
+    fn lookup_subroutine(name: &str, argument_count: usize, subroutines: &HashMap<String, (usize, usize)>, span: Span) -> Result<Option<usize>, Diagnostic> {
+        let (subroutine_idx, parameter_count) = match subroutines.get(name) {
+            Some(subroutine) => *subroutine,
+            None => return Ok(None),
+        };
+
+        if argument_count != parameter_count {
+            return Err(Diagnostic::parser(
+                format!("Subroutine '{}' takes {} argument(s) but {} were given", name, parameter_count, argument_count),
+                span,
+            ).with_label("wrong number of arguments"));
+        }
+
+        Ok(Some(subroutine_idx))
+    }
+    fn undefined_subroutine(&self, mut undefined_calls: Vec<(String, Span)>) -> Diagnostic {
+        // the first one in the source gets the snippet and every place it is called from
+        undefined_calls.sort_by_key(|(_, span)| span.start.offset);
 
-                token_idx += 1;
+        let mut names: Vec<&String> = vec![];
+
+        for (name, _) in &undefined_calls {
+            if !names.contains(&name) {
+                names.push(name);
             }
+        }
+
+        let call_spans: Vec<Span> = undefined_calls.iter().filter(
+            |(call_name, _)| call_name == names[0]
+        ).map(|(_, span)| *span).collect();
+        let message = match names.len() {
+            1 => format!("Call to undefined subroutine '{}'", names[0]),
+            count => format!("Call to undefined subroutine '{}' and {} more", names[0], count - 1),
+        };
+        let mut diagnostic = Diagnostic::parser(message, call_spans[0]).with_label("no subroutine with this name");
+
+        for span in &call_spans[1..] {
+            diagnostic = diagnostic.with_note(&format!("also called at line {}:{}", span.start.line, span.start.column));
+        }
 
-            if value_tokens.len() - 1 != operator_tokens.len() {
-                panic!(
-                    "LINE {} | Invalid token length of {} passed to resolve_any_value!",
-                    tokens.first().unwrap().src_line,
-                    tokens.len(),
-                );
+        // the rest get a note each, listing every place they are called from
+        for name in &names[1..] {
+            let call_sites: Vec<String> = undefined_calls.iter().filter(|(call_name, _)| call_name == *name).map(
+                |(_, span)| format!("line {}:{}", span.start.line, span.start.column)
+            ).collect();
+            let mut note = format!("'{}' is undefined too, called at {}", name, call_sites.join(", "));
+
+            if let Some(suggestion) = closest_match(name, self.subroutine_table.keys()) {
+                note += &format!(", did you mean `{}`?", suggestion);
             }
 
-            return Value::Expression {
-                values: value_tokens,
-                operators: operator_tokens
-            };
-        } else {
-            // must be 0
-            panic!("No tokens passed to resolve_any_value!");
+            diagnostic = diagnostic.with_note(&note);
+        }
+
+        match closest_match(names[0], self.subroutine_table.keys()) {
+            Some(suggestion) => diagnostic.with_help(&format!("did you mean `{}`?", suggestion)),
+            None => diagnostic,
         }
     }
-    fn unpack_expression(expression: &Value) -> (Vec<Value>, Vec<Operator>) {
-        match expression {
-            Value::Expression { values, operators } => {
-                return (values.to_owned(), operators.to_owned());
+    fn resolve_calls_in_value(
+        value: &mut Value, subroutines: &HashMap<String, (usize, usize)>, undefined_calls: &mut Vec<(String, Span)>
+    ) -> Result<(), Diagnostic> {
+        match value {
+            Value::Call { name, arguments, subroutine_idx, span } => {
+                *subroutine_idx = ASTGenerator::lookup_subroutine(name, arguments.len(), subroutines, *span)?;
+
+                if subroutine_idx.is_none() {
+                    undefined_calls.push((name.to_owned(), *span));
+                }
+
+                for argument in arguments {
+                    ASTGenerator::resolve_calls_in_value(argument, subroutines, undefined_calls)?;
+                }
             }
-            _ => {
-                return (vec![expression.to_owned()], vec![]);
+            Value::Unary { value, .. } => {
+                ASTGenerator::resolve_calls_in_value(value, subroutines, undefined_calls)?;
             }
+            Value::Expression { lhs, rhs, .. } => {
+                ASTGenerator::resolve_calls_in_value(lhs, subroutines, undefined_calls)?;
+                ASTGenerator::resolve_calls_in_value(rhs, subroutines, undefined_calls)?;
+            }
+            Value::Array(values) => {
+                for value in values {
+                    ASTGenerator::resolve_calls_in_value(value, subroutines, undefined_calls)?;
+                }
+            }
+            Value::MethodCall { receiver, arguments, .. } => {
+                ASTGenerator::resolve_calls_in_value(receiver, subroutines, undefined_calls)?;
+
+                for argument in arguments {
+                    ASTGenerator::resolve_calls_in_value(argument, subroutines, undefined_calls)?;
+                }
+            }
+            _ => {}
         }
+
+        Ok(())
     }
-    fn token_is_comparison_like(token: &WrappedToken) -> bool {
-        match token.token {
-            Token::Equals |
-            Token::NotEquals |
-            Token::MoreThan |
-            Token::LessThan => true,
-            _ => false,
+    fn list_length(list: &Option<Value>) -> usize {
+        match list {
+            Some(Value::Array(values)) => values.len(),
+            _ => 0,
         }
     }
     fn token_is_assign_op_like(token: &WrappedToken) -> bool {
-        match token.token {
-            Token::PlusEquals |
-            Token::MinusEquals => true,
-            _ => false,
-        }
+        matches!(
+            token.token,
+            Token::PlusEquals | Token::MinusEquals | Token::MultiplyEquals | Token::DivideEquals | Token::ModuloEquals
+        )
     }
     fn token_is_assign_like(token: &WrappedToken) -> bool {
-        match token.token {
-            Token::Assign => true,
-            _ => false,
-        }
+        token.token == Token::Assign
     }
     fn token_is_scope_like(token: &WrappedToken) -> bool {
-        match token.token {
-            Token::ScopeOpen => true,
-            _ => false,
-        }
+        token.token == Token::ScopeOpen
     }
     fn token_is_line_end(token: &WrappedToken) -> bool {
-        match token.token {
-            Token::LineEnd => true,
-            _ => false,
-        }
+        token.token == Token::LineEnd
     }
     fn insert_root_ast_scope(&mut self, new_token: ASTToken) {
         self.generated_ast.push(new_token);
@@ -450,50 +535,71 @@ impl ASTGenerator {
         self.generated_ast.push(new_token);
         self.scope_open_idxs.push(self.generated_ast.len() - 1); // new scope's index
     }
-    fn insert_new_empty_ast_scope(&mut self, src_line: usize) {
+    fn insert_new_empty_ast_scope(&mut self, span: Span) {
         self.insert_new_ast_scope(
             ASTToken::new_scope(
                 self.generated_ast.len() + 1, // point to index after scope open
-                src_line,
+                span,
             )
         );
     }
     fn insert_ast_token_at_end(&mut self, new_token: ASTToken) {
         self.generated_ast.push(new_token);
     }
-    fn insert_subroutine(&mut self, subroutine_name: String) {
-        self.insert_ast_token_at_end(ASTToken::of_type(
+    fn insert_subroutine(&mut self, subroutine_name: String, parameters: Vec<Value>, span: Span) -> Result<(), Diagnostic> {
+        if let Some(existing_idx) = self.subroutine_table.get(&subroutine_name) {
+            let first_span = self.generated_ast[existing_idx - 1].span;
+
+            return Err(
+                Diagnostic::parser(format!("Subroutine '{}' is defined more than once", subroutine_name), span)
+                    .with_label("defined again here")
+                    .with_note(&format!("first defined at line {}:{}", first_span.start.line, first_span.start.column))
+                    .with_help("rename one of them, a later definition does not replace an earlier one")
+            );
+        }
+
+        // the name is only kept for error messages
+        self.insert_ast_token_at_end(ASTToken::with_args(
             Statement::SubroutineDefine,
-            0,
+            Value::Array(parameters),
+            Some(Value::StringLiteral(subroutine_name.to_owned())),
+            span,
         ));
         // index after definition, so the interpreter doesn't skip
         self.subroutine_table.insert(subroutine_name.to_owned(), self.generated_ast.len());
+        Ok(())
     }
-    fn insert_subroutine_call(&mut self, subroutine_name: String, src_line: usize) {
-        self.insert_ast_token_at_end(ASTToken::of_type(
+    fn insert_subroutine_call(&mut self, subroutine_name: String, arguments: Vec<Value>, span: Span) {
+        self.insert_ast_token_at_end(ASTToken::with_args(
             Statement::SubroutineCall(None),
-            src_line,
+            Value::Array(arguments),
+            None,
+            span,
         ));
         self.subroutine_calls.entry(
             subroutine_name
-        ).or_insert_with(
-            Vec::new
-        ).push(
+        ).or_default().push(
             self.generated_ast.len() - 1
         );
     }
-    pub fn generate_ast(&mut self) {
-        self.insert_root_ast_scope(ASTToken::empty(0)); // root scope
+    pub fn generate_ast(&mut self) -> Result<(), Diagnostic> {
+        self.insert_root_ast_scope(ASTToken::empty(Span::default())); // root scope
 
         while self.current_token_idx < self.tokens.len() {
             let current_token = self.get_token().to_owned();
 
             match &current_token.token {
                 Token::ScopeOpen => {
-                    self.insert_new_empty_ast_scope(current_token.src_line);
+                    self.insert_new_empty_ast_scope(current_token.span);
                 }
                 Token::ScopeClose => {
-                    let closing_scope_idx = self.scope_open_idxs.pop().unwrap();
+                    let closing_scope_idx = match self.scope_open_idxs.pop() {
+                        Some(closing_scope_idx) => closing_scope_idx,
+                        None => return Err(
+                            Diagnostic::parser("Unmatched '}'".to_string(), current_token.span)
+                                .with_label("no `{` to close")
+                        ),
+                    };
 
                     if self.generated_ast[closing_scope_idx - 1].t_type == Statement::SubroutineDefine {
                         // this is closing a function call, ensure the last token is return
@@ -503,329 +609,517 @@ impl ASTGenerator {
                                 Statement::SubroutineReturn,
                                 Value::BoolLiteral(false),
                                 None,
-                                0,
+                                current_token.span,
                             ));
                         }
                     }
                     self.generated_ast[closing_scope_idx].body_extent = Some(
                         self.generated_ast.len() - closing_scope_idx
                     );
-                    self.insert_ast_token_at_end(ASTToken::of_type(Statement::BlockEnd, current_token.src_line));
+                    self.insert_ast_token_at_end(ASTToken::of_type(Statement::BlockEnd, current_token.span));
+
+                    let scope_owner = &self.generated_ast[closing_scope_idx - 1];
+
+                    if scope_owner.t_type == Statement::If || (scope_owner.t_type == Statement::Else && scope_owner.arg1.is_some()) {
+                        // the chain may continue
+                        self.else_allowed_at = Some(self.generated_ast.len());
+                    }
+
+                    if self.lowered_for_loops.last() == Some(&(closing_scope_idx - 1)) {
+                        // close the scope holding the loop's hidden variables too
+                        self.lowered_for_loops.pop();
+
+                        let outer_scope_idx = self.scope_open_idxs.pop().unwrap();
+                        self.generated_ast[outer_scope_idx].body_extent = Some(
+                            self.generated_ast.len() - outer_scope_idx
+                        );
+                        self.insert_ast_token_at_end(ASTToken::of_type(Statement::BlockEnd, current_token.span));
+                    }
                 }
-                Token::EOF => {
+                Token::Eof => {
                     self.scope_open_idxs.pop();
-                    self.insert_ast_token_at_end(ASTToken::of_type(Statement::EOF, 0));
+                    self.insert_ast_token_at_end(ASTToken::of_type(Statement::Eof, current_token.span));
                 }
                 Token::SubroutineCall => {
                     let subroutine_name = ASTGenerator::resolve_variable_name_like_token(
-                        self.advance_and_get_token()
-                    ).unwrap();
+                        self.advance_and_get_token()?
+                    )?;
+                    let mut arguments: Vec<Value> = vec![];
+
+                    if self.peek_next_token().is_some_and(|token| token.token == Token::ParensOpen) {
+                        arguments = ExpressionParser::parse_list(&self.advance_and_gather_parenthesized_tokens()?)?;
+                    }
 
-                    if self.peek_next_token().unwrap_or(
-                        &WrappedToken::from(Token::LineEnd)
-                    ).token == Token::LineEnd {
+                    if self.peek_next_token().is_none_or(|token| token.token == Token::LineEnd) {
                         // line end after sub name, just insert sub call
                         self.insert_subroutine_call(
-                            subroutine_name, current_token.src_line
+                            subroutine_name, arguments, self.statement_span(&current_token)
                         );
                     } else {
                         // check for -> and variable name to assign return to
-                        if self.advance_and_get_token().token != Token::SubroutineDirect {
-                            panic!("LINE {} | {:?} passed as redirect to SubroutineCall!", current_token.src_line, current_token.token);
+                        let redirect_token = self.advance_and_get_token()?.to_owned();
+
+                        if redirect_token.token != Token::SubroutineDirect {
+                            return Err(Diagnostic::parser(
+                                format!("Expected `->` after subroutine name, found {}", redirect_token.token),
+                                redirect_token.span,
+                            ).with_label("expected `->` here").with_help(
+                                "use `call name(arguments);` or `call name(arguments)->variable;`"
+                            ));
                         }
                         self.insert_subroutine_call(
-                            subroutine_name, current_token.src_line
+                            subroutine_name, arguments, self.statement_span(&current_token)
                         );
                         // get the variable to assign to
                         let variable_expression: Value = ASTGenerator::resolve_variable_write_like_token(
-                            self.advance_and_get_token()
-                        );
+                            self.advance_and_get_token()?
+                        )?;
                         // assign the special Return token to the variable
                         let new_token = ASTToken::with_args(
                             Statement::Set,
                             variable_expression,
                             Some(Value::Return),
-                            current_token.src_line,
+                            self.statement_span(&current_token),
                         );
                         self.insert_ast_token_at_end(new_token);
                     }
 
-                    assert_eq!(self.peek_next_token().unwrap().token, Token::LineEnd);
+                    self.expect_next_token(Token::LineEnd)?;
                 }
                 Token::SubroutineReturn => {
-                    let new_token: ASTToken;
+                    let in_subroutine = self.scope_open_idxs.iter().any(
+                        |scope_open_idx| self.generated_ast[scope_open_idx - 1].t_type == Statement::SubroutineDefine
+                    );
 
-                    if self.peek_next_token().unwrap().token != Token::LineEnd {
-                        let value_token = ASTGenerator::resolve_any_value(self.advance_and_gather_tokens_for_value());
-                        let (values, operators) = ASTGenerator::unpack_expression(&value_token);
+                    if !in_subroutine {
+                        return Err(Diagnostic::parser("`ret` outside of a subroutine".to_string(), current_token.span)
+                            .with_label("not inside a `sub` body")
+                            .with_help("the top level of a script cannot return, it ends after its last statement"));
+                    }
 
-                        if operators.len() == 0 {
-                            new_token = ASTToken::with_args(
-                                Statement::SubroutineReturn,
-                                values.get(0).unwrap().to_owned(),
-                                None,
-                                current_token.src_line,
-                            );
-                        } else {
-                            new_token = ASTToken::with_args(
-                                Statement::SubroutineReturn,
-                                Value::Expression { values: values, operators: operators },
-                                None,
-                                current_token.src_line,
-                            );
-                        }
+                    let value_token = if self.peek_next_token().map(|token| &token.token) != Some(&Token::LineEnd) {
+                        ASTGenerator::resolve_any_value(self.advance_and_gather_tokens_for_value()?)?
                     } else {
                         // return false if no value was passed to ret
-                        new_token = ASTToken::with_args(
-                            Statement::SubroutineReturn,
-                            Value::BoolLiteral(false),
-                            None,
-                            current_token.src_line,
-                        );
-                    }
+                        Value::BoolLiteral(false)
+                    };
+                    let new_token = ASTToken::with_args(
+                        Statement::SubroutineReturn,
+                        value_token,
+                        None,
+                        self.statement_span(&current_token),
+                    );
 
                     self.insert_ast_token_at_end(new_token);
-                    assert_eq!(self.peek_next_token().unwrap().token, Token::LineEnd);
+                    self.expect_next_token(Token::LineEnd)?;
                 }
                 Token::SubroutineDefine => {
                     // name of new subroutine
                     let subroutine_name = ASTGenerator::resolve_variable_name_like_token(
-                        self.advance_and_get_token()
-                    ).unwrap();
+                        self.advance_and_get_token()?
+                    )?;
+                    let mut parameters: Vec<Value> = vec![];
+
+                    if self.peek_next_token().is_some_and(|token| token.token == Token::ParensOpen) {
+                        parameters = self.advance_and_gather_parameters()?;
+                    }
+
                     // add subroutine token to stack
-                    self.insert_subroutine(subroutine_name);
+                    self.insert_subroutine(subroutine_name, parameters, self.statement_span(&current_token))?;
                     // check for block to execute after if statement
-                    assert_eq!(self.peek_next_token().unwrap().token, Token::ScopeOpen);
-                    self.insert_new_empty_ast_scope(current_token.src_line);
+                    self.expect_next_token(Token::ScopeOpen)?;
                     self.advance_token(); // skip scope open
+                    self.insert_new_empty_ast_scope(self.get_token().span);
                 }
                 Token::If => {
-                    let value_token = ASTGenerator::resolve_any_value(self.advance_and_gather_tokens_for_value());
-                    let (values, operators) = ASTGenerator::unpack_expression(&value_token);
-                    let new_token: ASTToken;
+                    let condition = ASTGenerator::resolve_any_value(self.advance_and_gather_tokens_for_value()?)?;
+                    let new_token = ASTToken::with_args_and_body(
+                        Statement::If,
+                        condition,
+                        None,
+                        self.generated_ast.len() + 1,
+                        self.statement_span(&current_token),
+                    );
 
-                    if operators.len() == 0 {
-                        // implicit bool
-                        new_token = ASTToken::with_args_and_body(
-                            Statement::If(Operator::Equals),
-                            values[0].to_owned(),
-                            Some(Value::BoolLiteral(true)),
+                    // add new token to stack
+                    self.insert_ast_token_at_end(new_token);
+                    // check for block to execute after if statement
+                    self.expect_next_token(Token::ScopeOpen)?;
+                    self.advance_token(); // skip scope open
+                    self.insert_new_empty_ast_scope(self.get_token().span);
+                }
+                Token::Else | Token::Elif => {
+                    if self.else_allowed_at != Some(self.generated_ast.len()) {
+                        return Err(Diagnostic::parser(
+                            format!("{} without a preceding `if`", current_token.token),
+                            current_token.span,
+                        ).with_label("not directly after an `if` block").with_help(
+                            "move it right after the closing `}` of an `if` or `else if`"
+                        ));
+                    }
+
+                    let is_else_if = current_token.token == Token::Elif || self.peek_next_token().is_some_and(
+                        |token| token.token == Token::If
+                    );
+                    let new_token = if is_else_if {
+                        if current_token.token == Token::Else {
+                            self.advance_token(); // skip if
+                        }
+
+                        let condition = ASTGenerator::resolve_any_value(self.advance_and_gather_tokens_for_value()?)?;
+
+                        ASTToken::with_args_and_body(
+                            Statement::Else,
+                            condition,
+                            None,
                             self.generated_ast.len() + 1,
-                            current_token.src_line,
-                        );
+                            self.statement_span(&current_token),
+                        )
                     } else {
-                        new_token = ASTToken::with_args_and_body(
-                            Statement::If(operators[0].to_owned()),
-                            values[0].to_owned(),
-                            Some(values[1].to_owned()),
+                        ASTToken::with_body(
+                            Statement::Else,
                             self.generated_ast.len() + 1,
-                            current_token.src_line,
-                        );
-                    }
+                            self.statement_span(&current_token),
+                        )
+                    };
 
                     // add new token to stack
                     self.insert_ast_token_at_end(new_token);
                     // check for block to execute after if statement
-                    assert_eq!(self.peek_next_token().unwrap().token, Token::ScopeOpen);
-                    self.insert_new_empty_ast_scope(current_token.src_line);
+                    self.expect_next_token(Token::ScopeOpen)?;
                     self.advance_token(); // skip scope open
+                    self.insert_new_empty_ast_scope(self.get_token().span);
                 }
-                Token::Else => {
-                    let new_token = ASTToken::with_body(
-                        Statement::Else,
+                Token::While => {
+                    let condition = ASTGenerator::resolve_any_value(self.advance_and_gather_tokens_for_value()?)?;
+                    let new_token = ASTToken::with_args_and_body(
+                        Statement::While,
+                        condition,
+                        self.loop_label.take().map(Value::StringLiteral),
                         self.generated_ast.len() + 1,
-                        current_token.src_line,
+                        self.statement_span(&current_token),
                     );
 
                     // add new token to stack
                     self.insert_ast_token_at_end(new_token);
                     // check for block to execute after if statement
-                    assert_eq!(self.peek_next_token().unwrap().token, Token::ScopeOpen);
-                    self.insert_new_empty_ast_scope(current_token.src_line);
+                    self.expect_next_token(Token::ScopeOpen)?;
                     self.advance_token(); // skip scope open
+                    self.insert_new_empty_ast_scope(self.get_token().span);
                 }
-                Token::While => {
-                    let value_token = ASTGenerator::resolve_any_value(self.advance_and_gather_tokens_for_value());
-                    let (values, operators) = ASTGenerator::unpack_expression(&value_token);
-                    let new_token: ASTToken;
+                Token::For => {
+                    // lowered onto a while inside a scope of its own:
+                    // { #index = 0; #end = iterable.; while #index < #end { alloc x = iterable|#index|; #index += 1; ... } }
+                    let variable_token = self.advance_and_get_token()?.to_owned();
+                    let variable_name = ASTGenerator::resolve_variable_name_like_token(&variable_token)?;
+                    let variable_span = variable_token.span;
+                    self.expect_next_token(Token::In)?;
+                    self.advance_token(); // skip in
+
+                    let iterable_tokens = self.advance_and_gather_tokens_for_value()?;
+                    let span = self.statement_span(&current_token);
+
+                    self.insert_new_empty_ast_scope(span);
+
+                    // '#' cannot appear in an identifier, so these never clash with user variables
+                    let hidden_prefix = format!("for#{}", self.generated_ast.len());
+                    let index = Value::Variable(format!("{}#index", hidden_prefix), span);
+                    let end = Value::Variable(format!("{}#end", hidden_prefix), span);
+                    let element = match iterable_tokens.iter().position(|token| token.token == Token::Range) {
+                        Some(range_idx) => {
+                            // `start..end`, the end is exclusive
+                            let range_span = iterable_tokens[range_idx].span;
+
+                            if range_idx == 0 {
+                                return Err(Diagnostic::parser("Range is missing its start".to_string(), range_span)
+                                    .with_label("expected a value before `..`")
+                                    .with_help("ranges need both ends, like `0..5`"));
+                            }
 
-                    if operators.len() == 0 {
-                        // implicit bool
-                        new_token = ASTToken::with_args_and_body(
-                            Statement::While(Operator::Equals),
-                            values[0].to_owned(),
-                            Some(Value::BoolLiteral(true)),
-                            self.generated_ast.len() + 1,
-                            current_token.src_line,
-                        );
-                    } else {
-                        new_token = ASTToken::with_args_and_body(
-                            Statement::While(operators[0].to_owned()),
-                            values[0].to_owned(),
-                            Some(values[1].to_owned()),
-                            self.generated_ast.len() + 1,
-                            current_token.src_line,
-                        );
-                    }
+                            if range_idx + 1 == iterable_tokens.len() {
+                                return Err(Diagnostic::parser("Range is missing its end".to_string(), range_span)
+                                    .with_label("expected a value after `..`")
+                                    .with_help("ranges need both ends, like `0..5`"));
+                            }
+
+                            let start_value = ASTGenerator::resolve_any_value(iterable_tokens[..range_idx].to_vec())?;
+                            let end_value = ASTGenerator::resolve_any_value(iterable_tokens[range_idx + 1..].to_vec())?;
+
+                            self.insert_ast_token_at_end(ASTToken::with_args(Statement::Set, index.to_owned(), Some(start_value), span));
+                            self.insert_ast_token_at_end(ASTToken::with_args(Statement::Set, end.to_owned(), Some(end_value), span));
+                            index.to_owned()
+                        }
+                        None => {
+                            // arrays and strings, evaluated once so the body cannot change what is iterated
+                            let iterable = Value::Variable(format!("{}#iterable", hidden_prefix), span);
+                            let iterable_value = ASTGenerator::resolve_any_value(iterable_tokens)?;
+
+                            self.insert_ast_token_at_end(ASTToken::with_args(Statement::Set, iterable.to_owned(), Some(iterable_value), span));
+                            self.insert_ast_token_at_end(ASTToken::with_args(Statement::Set, index.to_owned(), Some(Value::IntegerLiteral(0)), span));
+                            self.insert_ast_token_at_end(ASTToken::with_args(Statement::Set, end.to_owned(), Some(Value::Expression {
+                                operator: Operator::LenAccess,
+                                lhs: Box::new(iterable.to_owned()),
+                                rhs: Box::new(Value::Null),
+                                span,
+                            }), span));
+                            Value::Expression {
+                                operator: Operator::ArrayAccess,
+                                lhs: Box::new(iterable),
+                                rhs: Box::new(index.to_owned()),
+                                span,
+                            }
+                        }
+                    };
+
+                    let while_idx = self.generated_ast.len();
+                    let new_token = ASTToken::with_args_and_body(
+                        Statement::While,
+                        Value::Expression {
+                            operator: Operator::LessThan,
+                            lhs: Box::new(index.to_owned()),
+                            rhs: Box::new(end),
+                            span,
+                        },
+                        self.loop_label.take().map(Value::StringLiteral),
+                        while_idx + 1,
+                        span,
+                    );
 
-                    // add new token to stack
                     self.insert_ast_token_at_end(new_token);
-                    // check for block to execute after if statement
-                    assert_eq!(self.peek_next_token().unwrap().token, Token::ScopeOpen);
-                    self.insert_new_empty_ast_scope(current_token.src_line);
+                    self.lowered_for_loops.push(while_idx);
+                    // check for block to execute after for statement
+                    self.expect_next_token(Token::ScopeOpen)?;
                     self.advance_token(); // skip scope open
+                    self.insert_new_empty_ast_scope(self.get_token().span);
+
+                    // the loop variable lives in the body, the index moves on first so continue works
+                    self.insert_ast_token_at_end(ASTToken::with_args(
+                        Statement::Shadow, Value::Variable(variable_name, variable_span), Some(element), span
+                    ));
+                    self.insert_ast_token_at_end(ASTToken::with_args(Statement::Set, index.to_owned(), Some(Value::Expression {
+                        operator: Operator::Add,
+                        lhs: Box::new(index),
+                        rhs: Box::new(Value::IntegerLiteral(1)),
+                        span,
+                    }), span));
                 }
                 Token::Alloc => {
                     // get the variable to assign to
                     let variable_expression: Value = ASTGenerator::resolve_variable_write_like_token(
-                        self.advance_and_get_token()
-                    );
+                        self.advance_and_get_token()?
+                    )?;
 
                     // make sure the = is there
-                    if !ASTGenerator::token_is_assign_like(self.advance_and_get_token()) {
-                        panic!("LINE {} | {:?} passed as value to alloc!", current_token.src_line, current_token.token);
+                    let assign_token = self.advance_and_get_token()?.to_owned();
+
+                    if !ASTGenerator::token_is_assign_like(&assign_token) {
+                        return Err(Diagnostic::parser(
+                            format!("Expected `=` after variable name, found {}", assign_token.token),
+                            assign_token.span,
+                        ).with_label("expected `=` here").with_help(
+                            "use `alloc name = value;`"
+                        ));
                     }
 
-                    let value_token = ASTGenerator::resolve_any_value(self.advance_and_gather_tokens_for_value());
-                    let (values, operators) = ASTGenerator::unpack_expression(&value_token);
-                    let new_token: ASTToken;
-
-                    if operators.len() == 0 {
-                        new_token = ASTToken::with_args(
-                            Statement::Alloc,
-                            variable_expression,
-                            Some(values.get(0).unwrap().to_owned()),
-                            current_token.src_line,
-                        );
-                    } else {
-                        new_token = ASTToken::with_args(
-                            Statement::Alloc,
-                            variable_expression,
-                            Some(Value::Expression { values: values, operators: operators }),
-                            current_token.src_line,
-                        );
-                    }
+                    let value_token = ASTGenerator::resolve_any_value(self.advance_and_gather_tokens_for_value()?)?;
+                    let new_token = ASTToken::with_args(
+                        Statement::Alloc,
+                        variable_expression,
+                        Some(value_token),
+                        self.statement_span(&current_token),
+                    );
 
                     self.insert_ast_token_at_end(new_token);
                     // check for line end, alloc takes a fixed amount of args
-                    assert_eq!(self.peek_next_token().unwrap().token, Token::LineEnd);
+                    self.expect_next_token(Token::LineEnd)?;
                 }
                 Token::Set => {
-                    // get the variable to assign to
-                    let variable_expression: Value = ASTGenerator::resolve_variable_write_like_token(
-                        self.advance_and_get_token()
-                    );
+                    // get the variable or element to assign to
+                    let variable_expression: Value = self.advance_and_resolve_assign_target(vec![])?;
 
                     // make sure the = is there
-                    if !ASTGenerator::token_is_assign_like(self.advance_and_get_token()) {
-                        panic!("LINE {} | {:?} passed as value to set!", current_token.src_line, current_token.token);
+                    let assign_token = self.advance_and_get_token()?.to_owned();
+
+                    if !ASTGenerator::token_is_assign_like(&assign_token) {
+                        return Err(Diagnostic::parser(
+                            format!("Expected `=` after variable name, found {}", assign_token.token),
+                            assign_token.span,
+                        ).with_label("expected `=` here").with_help(
+                            "use `set name = value;`"
+                        ));
                     }
 
-                    let value_token = ASTGenerator::resolve_any_value(self.advance_and_gather_tokens_for_value());
-                    let (values, operators) = ASTGenerator::unpack_expression(&value_token);
+                    let value_token = ASTGenerator::resolve_any_value(self.advance_and_gather_tokens_for_value()?)?;
+                    let new_token = ASTToken::with_args(
+                        Statement::Set,
+                        variable_expression,
+                        Some(value_token),
+                        self.statement_span(&current_token),
+                    );
+
+                    self.insert_ast_token_at_end(new_token);
+                    // check for line end, set takes a fixed amount of args
+                    self.expect_next_token(Token::LineEnd)?;
+                }
+                Token::Variable(label) if self.peek_next_token().is_some_and(|token| token.token == Token::Colon) => {
+                    // `label: while ... { }`, for break and continue
+                    self.advance_token(); // skip colon
+
+                    if !self.peek_next_token().is_some_and(|token| token.token == Token::While || token.token == Token::For) {
+                        self.expect_next_token(Token::While)?;
+                    }
+
+                    self.loop_label = Some(label.to_owned());
+                }
+                Token::Break | Token::Continue => {
+                    let mut label: Option<String> = None;
+
+                    if let Some(Token::Variable(name)) = self.peek_next_token().map(|token| &token.token) {
+                        label = Some(name.to_owned());
+                        self.advance_token();
+                    }
+
+                    let loop_idx = self.find_enclosing_loop(&label, self.statement_span(&current_token))?;
+                    let statement = match current_token.token {
+                        Token::Break => Statement::Break(loop_idx),
+                        _ => Statement::Continue(loop_idx),
+                    };
+
+                    self.insert_ast_token_at_end(ASTToken::of_type(statement, self.statement_span(&current_token)));
+                    self.expect_next_token(Token::LineEnd)?;
+                }
+                Token::Variable(_) => {
                     let new_token: ASTToken;
+                    let (variable_expression, head_span) = self.advance_and_parse_statement_head(vec![current_token.to_owned()])?;
 
-                    if operators.len() == 0 {
+                    // `arr.push(1);`
+                    let is_method_statement = matches!(variable_expression, Value::MethodCall { .. })
+                        && self.peek_next_token().is_some_and(ASTGenerator::token_is_line_end);
+
+                    if !is_method_statement {
+                        ASTGenerator::check_assign_target(&variable_expression, head_span)?;
+                    }
+
+                    if is_method_statement {
                         new_token = ASTToken::with_args(
-                            Statement::Set,
+                            Statement::Evaluate,
                             variable_expression,
-                            Some(values.get(0).unwrap().to_owned()),
-                            current_token.src_line,
+                            None,
+                            self.statement_span(&current_token),
                         );
-                    } else {
+                    } else if self.peek_next_token().is_some_and(ASTGenerator::token_is_assign_like) && !matches!(variable_expression, Value::Variable(..)) {
+                        // `arr|i| = value;`, plain variables still need set
+                        self.advance_token(); // skip =
+
+                        let value_token = ASTGenerator::resolve_any_value(self.advance_and_gather_tokens_for_value()?)?;
+
                         new_token = ASTToken::with_args(
                             Statement::Set,
                             variable_expression,
-                            Some(Value::Expression { values: values, operators: operators }),
-                            current_token.src_line,
-                        );
-                    }
-
-                    self.insert_ast_token_at_end(new_token);
-                    // check for line end, set takes a fixed amount of args
-                    assert_eq!(self.peek_next_token().unwrap().token, Token::LineEnd);
-                }
-                Token::Variable(_) => {
-                    let new_token: ASTToken;
-
-                    if ASTGenerator::token_is_assign_op_like(self.peek_next_token().unwrap()) {
-                        // plus and minus equals operators
-                        let variable_expression: Value = ASTGenerator::resolve_variable_write_like_token(
-                            &current_token
+                            Some(value_token),
+                            self.statement_span(&current_token),
                         );
-                        let assign_op: WrappedToken;
+                    } else if self.peek_next_token().is_some_and(ASTGenerator::token_is_assign_op_like) {
+                        // compound assignment operators
+                        let assign_op: Operator;
 
-                        match self.advance_and_get_token().to_owned().token {
+                        match self.advance_and_get_token()?.to_owned().token {
                             Token::PlusEquals => {
-                                assign_op = WrappedToken::from(Token::Plus);
+                                assign_op = Operator::Add;
                             }
                             Token::MinusEquals => {
-                                assign_op = WrappedToken::from(Token::Minus);
+                                assign_op = Operator::Sub;
+                            }
+                            Token::MultiplyEquals => {
+                                assign_op = Operator::Mul;
+                            }
+                            Token::DivideEquals => {
+                                assign_op = Operator::Div;
+                            }
+                            Token::ModuloEquals => {
+                                assign_op = Operator::Mod;
                             }
                             _ => {
                                 unreachable!()
                             }
                         }
 
-                        let value_token = ASTGenerator::resolve_any_value(
-                            [
-                                vec![current_token.to_owned(), assign_op],
-                                self.advance_and_gather_tokens_for_value(),
-                            ].concat()
-                        );
-
-                        new_token = ASTToken::with_args(
-                            Statement::Set,
-                            variable_expression,
-                            Some(value_token),
-                            current_token.src_line,
-                        );
+                        // the whole right hand side is the operand, `x -= 1 + 2` subtracts 3
+                        let value_token = ASTGenerator::resolve_any_value(self.advance_and_gather_tokens_for_value()?)?;
+
+                        new_token = match variable_expression {
+                            Value::Variable(..) => ASTToken::with_args(
+                                Statement::Set,
+                                variable_expression.to_owned(),
+                                Some(Value::Expression {
+                                    operator: assign_op,
+                                    lhs: Box::new(variable_expression),
+                                    rhs: Box::new(value_token),
+                                    span: head_span.to(self.get_token().span),
+                                }),
+                                self.statement_span(&current_token),
+                            ),
+                            // reading the element again would evaluate its indexes twice
+                            _ => ASTToken::with_args(
+                                Statement::Update(assign_op),
+                                variable_expression,
+                                Some(value_token),
+                                self.statement_span(&current_token),
+                            ),
+                        };
                     } else {
-                        panic!("LINE {} | Mysterious variable at start of statement with no assign operator!", current_token.src_line);
+                        return Err(Diagnostic::parser(
+                            "Mysterious variable at start of statement with no assign operator".to_string(),
+                            current_token.span,
+                        ).with_label("expected an assign operator like `+=` after this").with_help(
+                            "use `set name = value;` to assign a variable"
+                        ));
                     }
 
                     self.insert_ast_token_at_end(new_token);
                     // check for line end, alloc takes a fixed amount of args
-                    assert_eq!(self.peek_next_token().unwrap().token, Token::LineEnd);
+                    self.expect_next_token(Token::LineEnd)?;
                 }
                 Token::Print => {
                     // debug printing, takes 1 variable-like argument
-                    let value_token = ASTGenerator::resolve_any_value(self.advance_and_gather_tokens_for_value());
-                    let (values, operators) = ASTGenerator::unpack_expression(&value_token);
-                    let new_token: ASTToken;
-
-                    if operators.len() == 0 {
-                        new_token = ASTToken::with_args(
-                            Statement::DebugPrintCall,
-                            values.get(0).unwrap().to_owned(),
-                            None,
-                            current_token.src_line,
-                        );
-                    } else {
-                        new_token = ASTToken::with_args(
-                            Statement::DebugPrintCall,
-                            Value::Expression { values: values, operators: operators },
-                            None,
-                            current_token.src_line,
-                        );
-                    }
+                    let value_token = ASTGenerator::resolve_any_value(self.advance_and_gather_tokens_for_value()?)?;
+                    let new_token = ASTToken::with_args(
+                        Statement::DebugPrintCall,
+                        value_token,
+                        None,
+                        self.statement_span(&current_token),
+                    );
 
                     self.insert_ast_token_at_end(new_token);
-                    assert_eq!(self.peek_next_token().unwrap().token, Token::LineEnd);
+                    self.expect_next_token(Token::LineEnd)?;
                 }
                 Token::ReadLine => {
                     // read line of input from terminal, takes 1 variable argument
                     let variable_expression: Value = ASTGenerator::resolve_variable_write_like_token(
-                        self.advance_and_get_token()
-                    );
+                        self.advance_and_get_token()?
+                    )?;
                     let new_token: ASTToken = ASTToken::with_args(
                         Statement::ReadLineCall,
                         variable_expression,
                         None,
-                        current_token.src_line,
+                        self.statement_span(&current_token),
+                    );
+                    self.insert_ast_token_at_end(new_token);
+                    self.expect_next_token(Token::LineEnd)?;
+                }
+                Token::Global => {
+                    // makes a global variable writable from inside a subroutine
+                    let variable_expression: Value = ASTGenerator::resolve_variable_write_like_token(
+                        self.advance_and_get_token()?
+                    )?;
+                    let new_token: ASTToken = ASTToken::with_args(
+                        Statement::Global,
+                        variable_expression,
+                        None,
+                        self.statement_span(&current_token),
                     );
                     self.insert_ast_token_at_end(new_token);
-                    assert_eq!(self.peek_next_token().unwrap().token, Token::LineEnd);
+                    self.expect_next_token(Token::LineEnd)?;
                 }
                 _ => {
 
@@ -836,16 +1130,51 @@ impl ASTGenerator {
         }
 
         // resolve subroutines
-        for (key, value) in &self.subroutine_calls {
+        let mut subroutines: HashMap<String, (usize, usize)> = HashMap::new();
+
+        for (name, subroutine_idx) in &self.subroutine_table {
+            let parameter_count = ASTGenerator::list_length(&self.generated_ast[subroutine_idx - 1].arg1);
+            subroutines.insert(name.to_owned(), (*subroutine_idx, parameter_count));
+        }
+
+        let mut undefined_calls: Vec<(String, Span)> = vec![];
+        let mut call_names: HashMap<usize, String> = HashMap::new();
+
+        for (key, value) in self.subroutine_calls.drain() {
             for call_idx in value {
-                self.generated_ast[*call_idx] = ASTToken::of_type(
-                    Statement::SubroutineCall(
-                        Some(*self.subroutine_table.get(key).unwrap())
-                    ),
-                    0,
-                )
+                call_names.insert(call_idx, key.to_owned());
+            }
+        }
+
+        // in program order, so the first bad call in the source is the one reported
+        for (ast_idx, ast_token) in self.generated_ast.iter_mut().enumerate() {
+            let span = ast_token.span;
+
+            if let Some(name) = call_names.get(&ast_idx) {
+                let subroutine_idx = ASTGenerator::lookup_subroutine(
+                    name,
+                    ASTGenerator::list_length(&ast_token.arg1),
+                    &subroutines,
+                    span,
+                )?;
+
+                if subroutine_idx.is_none() {
+                    undefined_calls.push((name.to_owned(), span));
+                }
+
+                ast_token.t_type = Statement::SubroutineCall(subroutine_idx);
+            }
+
+            // calls nested in expressions
+            for value in [&mut ast_token.arg1, &mut ast_token.arg2].into_iter().flatten() {
+                ASTGenerator::resolve_calls_in_value(value, &subroutines, &mut undefined_calls)?;
             }
         }
-        self.subroutine_calls.clear();
+
+        if !undefined_calls.is_empty() {
+            return Err(self.undefined_subroutine(undefined_calls));
+        }
+
+        Ok(())
     }
 }
@@ -0,0 +1,194 @@
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+
+use crate::tokenizer::Span;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Tokenizer,
+    Parser,
+    Runtime,
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Stage::Tokenizer => write!(f, "syntax"),
+            Stage::Parser => write!(f, "parse"),
+            Stage::Runtime => write!(f, "runtime"),
+        }
+    }
+}
+
+// boxed so the Err side of every Result stays one pointer wide
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic(Box<DiagnosticInfo>);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiagnosticInfo {
+    pub stage: Stage,
+    pub message: String,
+    // the stages only see lines, the host fills this in
+    pub file: Option<String>,
+    pub span: Span,
+    // short note printed under the caret
+    pub label: Option<String>,
+    pub help: Option<String>,
+    // extra context printed after the source snippet
+    pub notes: Vec<String>,
+}
+
+impl Deref for Diagnostic {
+    type Target = DiagnosticInfo;
+
+    fn deref(&self) -> &DiagnosticInfo {
+        &self.0
+    }
+}
+
+impl DerefMut for Diagnostic {
+    fn deref_mut(&mut self) -> &mut DiagnosticInfo {
+        &mut self.0
+    }
+}
+
+impl Diagnostic {
+    pub fn new(stage: Stage, message: String, span: Span) -> Self {
+        Self(Box::new(DiagnosticInfo { stage, message, file: None, span, label: None, help: None, notes: vec![] }))
+    }
+    pub fn tokenizer(message: String, span: Span) -> Self {
+        Self::new(Stage::Tokenizer, message, span)
+    }
+    pub fn parser(message: String, span: Span) -> Self {
+        Self::new(Stage::Parser, message, span)
+    }
+    pub fn runtime(message: String, span: Span) -> Self {
+        Self::new(Stage::Runtime, message, span)
+    }
+    pub fn in_file(mut self, file: &str) -> Self {
+        self.file = Some(file.to_string());
+        self
+    }
+    pub fn with_label(mut self, label: &str) -> Self {
+        self.label = Some(label.to_string());
+        self
+    }
+    pub fn with_help(mut self, help: &str) -> Self {
+        self.help = Some(help.to_string());
+        self
+    }
+    pub fn with_note(mut self, note: &str) -> Self {
+        self.notes.push(note.to_string());
+        self
+    }
+    // rustc-style report, with the offending source line and a caret underline
+    pub fn render(&self, src_lines: &[String]) -> String {
+        let line = self.span.start.line;
+        let gutter = " ".repeat(line.to_string().len());
+        let mut out = format!("{} error: {}\n", self.stage, self.message);
+
+        match &self.file {
+            Some(file) => out += &format!("{}--> {}:{}:{}\n", gutter, file, line, self.span.start.column),
+            None => out += &format!("{}--> line {}:{}\n", gutter, line, self.span.start.column),
+        }
+
+        if line == 0 || line > src_lines.len() {
+            // nothing to point at
+            for note in &self.notes {
+                out += &format!("{} = note: {}\n", gutter, note);
+            }
+
+            if let Some(help) = &self.help {
+                out += &format!("{} = help: {}\n", gutter, help);
+            }
+
+            return out;
+        }
+
+        let src_line = &src_lines[line - 1];
+        let line_length = src_line.chars().count();
+        let start_column = self.span.start.column.max(1);
+        // spans over several lines are underlined up to the end of the first one
+        let end_column = if self.span.end.line == line { self.span.end.column } else { line_length + 1 };
+        let underline_length = end_column.saturating_sub(start_column).max(1);
+        // keep tabs so the caret lines up with the source
+        let padding: String = src_line.chars().take(start_column - 1).map(
+            |c| if c == '\t' { '\t' } else { ' ' }
+        ).collect();
+
+        out += &format!("{} |\n", gutter);
+        out += &format!("{} | {}\n", line, src_line);
+        out += &format!("{} | {}{}", gutter, padding, "^".repeat(underline_length));
+
+        if let Some(label) = &self.label {
+            out += &format!(" {}", label);
+        }
+
+        out += "\n";
+
+        if !self.notes.is_empty() || self.help.is_some() {
+            out += &format!("{} |\n", gutter);
+        }
+
+        for note in &self.notes {
+            out += &format!("{} = note: {}\n", gutter, note);
+        }
+
+        if let Some(help) = &self.help {
+            out += &format!("{} = help: {}\n", gutter, help);
+        }
+
+        out
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.file {
+            Some(file) => write!(
+                f, "{}:{}:{}: {} error: {}", file, self.span.start.line, self.span.start.column, self.stage, self.message
+            ),
+            None => write!(
+                f, "line {}:{}: {} error: {}", self.span.start.line, self.span.start.column, self.stage, self.message
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Diagnostic {}
+
+// levenshtein distance, counted in chars
+fn edit_distance(first: &str, second: &str) -> usize {
+    let second_chars: Vec<char> = second.chars().collect();
+    let mut previous_row: Vec<usize> = (0..=second_chars.len()).collect();
+
+    for (first_idx, first_char) in first.chars().enumerate() {
+        let mut current_row = vec![first_idx + 1];
+
+        for (second_idx, second_char) in second_chars.iter().enumerate() {
+            let substitution = previous_row[second_idx] + if first_char == *second_char { 0 } else { 1 };
+            let insertion = current_row[second_idx] + 1;
+            let deletion = previous_row[second_idx + 1] + 1;
+
+            current_row.push(substitution.min(insertion).min(deletion));
+        }
+
+        previous_row = current_row;
+    }
+
+    previous_row[second_chars.len()]
+}
+
+// the candidate a typo of name most likely meant, if any is close enough
+pub fn closest_match<'a>(name: &str, candidates: impl Iterator<Item = &'a String>) -> Option<&'a String> {
+    let max_distance = (name.chars().count() / 3).max(1);
+
+    candidates
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        // ties go to the alphabetically first name so the suggestion is stable
+        .min_by(|(first_distance, first), (second_distance, second)| {
+            first_distance.cmp(second_distance).then(first.cmp(second))
+        })
+        .map(|(_, candidate)| candidate)
+}
@@ -0,0 +1,338 @@
+use crate::astgen::{Operator, Value};
+use crate::diagnostic::Diagnostic;
+use crate::methods;
+use crate::tokenizer::{Span, StringPart, Token, WrappedToken};
+
+// binding power of binary operators, higher binds tighter
+const OR: u8 = 1;
+const AND: u8 = 2;
+const COMPARISON: u8 = 3;
+const ADDITIVE: u8 = 4;
+const MULTIPLICATIVE: u8 = 5;
+
+pub struct ExpressionParser<'a> {
+    tokens: &'a [WrappedToken],
+    token_idx: usize,
+    // the last index was closed by the first half of a `||`
+    split_access: bool,
+}
+
+impl<'a> ExpressionParser<'a> {
+    // parses the whole token slice into a single value tree
+    pub fn parse(tokens: &'a [WrappedToken]) -> Result<Value, Diagnostic> {
+        let mut parser = Self { tokens, token_idx: 0, split_access: false };
+        let value = parser.parse_binary(0)?;
+
+        if let Some(token) = parser.peek_token() {
+            return Err(Diagnostic::parser(
+                format!("Unexpected {} in expression", token.token),
+                token.span,
+            ).with_label("expected an operator before this"));
+        }
+
+        Ok(value)
+    }
+    // comma separated values, for argument lists
+    pub fn parse_list(tokens: &'a [WrappedToken]) -> Result<Vec<Value>, Diagnostic> {
+        let mut parser = Self { tokens, token_idx: 0, split_access: false };
+        let mut values: Vec<Value> = vec![];
+
+        while parser.peek_token().is_some() {
+            values.push(parser.parse_binary(0)?);
+
+            match parser.peek_token() {
+                Some(token) if token.token == Token::Comma => parser.token_idx += 1,
+                Some(token) => return Err(Diagnostic::parser(
+                    format!("Unexpected {} in argument list", token.token),
+                    token.span,
+                ).with_label("expected `,` before this")),
+                None => break,
+            }
+        }
+
+        Ok(values)
+    }
+    fn peek_token(&self) -> Option<&'a WrappedToken> {
+        self.tokens.get(self.token_idx)
+    }
+    fn advance_and_get_token(&mut self) -> Result<&'a WrappedToken, Diagnostic> {
+        match self.tokens.get(self.token_idx) {
+            Some(token) => {
+                self.token_idx += 1;
+                Ok(token)
+            }
+            None => Err(self.expected_value()),
+        }
+    }
+    // from the token at start to the last one taken
+    fn span_since(&self, start: usize) -> Span {
+        self.tokens[start].span.to(self.tokens[self.token_idx - 1].span)
+    }
+    fn expected_value(&self) -> Diagnostic {
+        match self.tokens.last() {
+            Some(token) => Diagnostic::parser("Expected a value".to_string(), token.span)
+                .with_label("expected a value after this"),
+            None => Diagnostic::parser("Expected a value".to_string(), Default::default()),
+        }
+    }
+    fn binary_operator(token: &Token) -> Option<(Operator, u8)> {
+        match token {
+            Token::Or => Some((Operator::Or, OR)),
+            Token::And => Some((Operator::And, AND)),
+            Token::Equals => Some((Operator::Equals, COMPARISON)),
+            Token::NotEquals => Some((Operator::NotEquals, COMPARISON)),
+            Token::MoreThan => Some((Operator::MoreThan, COMPARISON)),
+            Token::LessThan => Some((Operator::LessThan, COMPARISON)),
+            Token::MoreThanOrEquals => Some((Operator::MoreThanOrEquals, COMPARISON)),
+            Token::LessThanOrEquals => Some((Operator::LessThanOrEquals, COMPARISON)),
+            Token::Plus => Some((Operator::Add, ADDITIVE)),
+            Token::Minus => Some((Operator::Sub, ADDITIVE)),
+            Token::Multiply => Some((Operator::Mul, MULTIPLICATIVE)),
+            Token::Divide => Some((Operator::Div, MULTIPLICATIVE)),
+            Token::Modulo => Some((Operator::Mod, MULTIPLICATIVE)),
+            _ => None,
+        }
+    }
+    fn parse_binary(&mut self, min_precedence: u8) -> Result<Value, Diagnostic> {
+        let start = self.token_idx;
+        let mut lhs = self.parse_unary()?;
+
+        while let Some(token) = self.peek_token() {
+            let (operator, precedence) = match ExpressionParser::binary_operator(&token.token) {
+                Some((operator, precedence)) if precedence >= min_precedence => (operator, precedence),
+                _ => break,
+            };
+
+            self.token_idx += 1;
+            // everything is left associative, so the right side only takes tighter operators
+            let rhs = self.parse_binary(precedence + 1)?;
+
+            lhs = Value::Expression {
+                operator,
+                lhs: Box::new(lhs),
+                rhs: Box::new(rhs),
+                span: self.span_since(start),
+            };
+        }
+
+        Ok(lhs)
+    }
+    fn parse_unary(&mut self) -> Result<Value, Diagnostic> {
+        let start = self.token_idx;
+        let operator = match self.peek_token().map(|token| &token.token) {
+            Some(Token::Minus) => Operator::Negate,
+            Some(Token::Not) => Operator::Not,
+            _ => return self.parse_postfix(),
+        };
+
+        self.token_idx += 1;
+
+        // `-9223372036854775808`, the only literal that cannot be negated after parsing
+        if operator == Operator::Negate && self.peek_token().is_some_and(|token| token.token == Token::IntegerLiteral(i64::MIN)) {
+            self.token_idx += 1;
+            return Ok(Value::IntegerLiteral(i64::MIN));
+        }
+
+        let value = self.parse_unary()?;
+
+        match (&operator, value) {
+            // fold negative literals, the tokenizer only produces positive ones
+            // `--9223372036854775808` is left for the interpreter to report the overflow
+            (Operator::Negate, Value::IntegerLiteral(literal)) if literal != i64::MIN => Ok(Value::IntegerLiteral(-literal)),
+            (Operator::Negate, Value::FloatLiteral(literal)) => Ok(Value::FloatLiteral(-literal)),
+            (_, value) => Ok(Value::Unary { operator, value: Box::new(value), span: self.span_since(start) }),
+        }
+    }
+    fn parse_postfix(&mut self) -> Result<Value, Diagnostic> {
+        let start = self.token_idx;
+        let mut value = self.parse_primary()?;
+
+        while let Some(token) = self.peek_token() {
+            if token.token == Token::LenAccess && self.method_call_follows() {
+                value = self.parse_method_call(value, start)?;
+                continue;
+            }
+
+            // `.pop` is the `pop` method, so it writes back through element paths the same way
+            let pop_name = match token.token {
+                Token::PopAccess => Some("pop"),
+                Token::PopFrontAccess => Some("popfront"),
+                _ => None,
+            };
+
+            if let Some(name) = pop_name {
+                self.token_idx += 1;
+                self.split_access = false;
+                value = Value::MethodCall {
+                    name: name.to_string(),
+                    receiver: Box::new(value),
+                    arguments: vec![],
+                    span: self.span_since(start),
+                };
+                continue;
+            }
+
+            let operator = match token.token {
+                Token::ArrayAccess => Operator::ArrayAccess,
+                Token::Or if self.split_access => Operator::ArrayAccess,
+                Token::LenAccess => Operator::LenAccess,
+                _ => break,
+            };
+
+            self.token_idx += 1;
+            self.split_access = false;
+
+            let rhs = match operator {
+                Operator::ArrayAccess => self.parse_index(token)?,
+                // accessors have no right hand side
+                _ => Value::Null,
+            };
+
+            value = Value::Expression {
+                operator,
+                lhs: Box::new(value),
+                rhs: Box::new(rhs),
+                span: self.span_since(start),
+            };
+        }
+
+        Ok(value)
+    }
+    // `.name(`, a bare `.` is the length accessor
+    fn method_call_follows(&self) -> bool {
+        matches!(self.tokens.get(self.token_idx + 1).map(|token| &token.token), Some(Token::Variable(_)))
+            && self.tokens.get(self.token_idx + 2).is_some_and(|token| token.token == Token::ParensOpen)
+    }
+    fn parse_method_call(&mut self, receiver: Value, start: usize) -> Result<Value, Diagnostic> {
+        self.token_idx += 1; // skip .
+
+        let name_token = self.advance_and_get_token()?;
+        let name = match &name_token.token {
+            Token::Variable(name) => name.to_owned(),
+            _ => unreachable!(),
+        };
+        let open_token = self.advance_and_get_token()?;
+        let arguments = self.parse_sequence(Token::ParensClose, open_token)?;
+
+        methods::check_call(&name, arguments.len(), name_token.span)?;
+
+        Ok(Value::MethodCall { name, receiver: Box::new(receiver), arguments, span: self.span_since(start) })
+    }
+    fn parse_index(&mut self, open_token: &WrappedToken) -> Result<Value, Diagnostic> {
+        // the index runs until the next |, it cannot contain another access
+        let index_start = self.token_idx;
+
+        while self.peek_token().is_some_and(|token| token.token != Token::ArrayAccess && token.token != Token::Or) {
+            self.token_idx += 1;
+        }
+
+        if self.peek_token().is_none() {
+            return Err(Diagnostic::parser("Missing closing `|`".to_string(), open_token.span)
+                .with_label("index opened here"));
+        } else if self.token_idx == index_start {
+            return Err(Diagnostic::parser("Expected an index".to_string(), open_token.span)
+                .with_label("empty index"));
+        }
+
+        let index = ExpressionParser::parse(&self.tokens[index_start..self.token_idx])?;
+
+        if self.peek_token().is_some_and(|token| token.token == Token::Or) {
+            // `arr|0||1|`, the `||` closes this index and opens the next
+            self.split_access = true;
+        } else {
+            self.token_idx += 1; // skip closing |
+        }
+
+        Ok(index)
+    }
+    fn parse_primary(&mut self) -> Result<Value, Diagnostic> {
+        let token = self.advance_and_get_token()?;
+
+        match &token.token {
+            Token::IntegerLiteral(i64::MIN) => Err(
+                Diagnostic::parser(format!("Integer literal {} is too large", i64::MIN.unsigned_abs()), token.span)
+                    .with_label(&format!("does not fit in {} bits", i64::BITS))
+                    .with_help(&format!("only `{}` fits, with the minus sign", i64::MIN))
+            ),
+            Token::IntegerLiteral(value) => Ok(Value::IntegerLiteral(value.to_owned())),
+            Token::FloatLiteral(value) => Ok(Value::FloatLiteral(value.to_owned())),
+            Token::StringLiteral(value) => Ok(Value::StringLiteral(value.to_owned())),
+            Token::InterpolatedString(parts) => ExpressionParser::parse_interpolation(parts, token.span),
+            Token::BoolTrue => Ok(Value::BoolLiteral(true)),
+            Token::BoolFalse => Ok(Value::BoolLiteral(false)),
+            Token::Null => Ok(Value::Null),
+            Token::Variable(value) if self.peek_token().is_some_and(|next_token| next_token.token == Token::ParensOpen) => {
+                let open_token = self.advance_and_get_token()?;
+                let arguments = self.parse_sequence(Token::ParensClose, open_token)?;
+
+                Ok(Value::Call {
+                    name: value.to_owned(),
+                    arguments,
+                    subroutine_idx: None,
+                    span: token.span.to(self.tokens[self.token_idx - 1].span),
+                })
+            }
+            Token::Variable(value) => Ok(Value::Variable(value.to_owned(), token.span)),
+            Token::ParensOpen => {
+                let value = self.parse_binary(0)?;
+                self.expect_closing(Token::ParensClose, token)?;
+                Ok(value)
+            }
+            Token::ArrayOpen => Ok(Value::Array(self.parse_sequence(Token::ArrayClose, token)?)),
+            _ => Err(Diagnostic::parser(
+                format!("Unexpected {} in expression", token.token),
+                token.span,
+            ).with_label("expected a value here")),
+        }
+    }
+    // `"a{b}c"` becomes `"a" + b + "c"`, the leading string makes every part convert to one
+    fn parse_interpolation(parts: &[StringPart], span: Span) -> Result<Value, Diagnostic> {
+        let mut value: Option<Value> = None;
+
+        for part in parts {
+            let part_value = match part {
+                StringPart::Literal(literal) if literal.is_empty() && value.is_some() => continue,
+                StringPart::Literal(literal) => Value::StringLiteral(literal.to_owned()),
+                StringPart::Expression(tokens) => ExpressionParser::parse(tokens)?,
+            };
+
+            value = Some(match value {
+                Some(lhs) => Value::Expression { operator: Operator::Add, lhs: Box::new(lhs), rhs: Box::new(part_value), span },
+                None => part_value,
+            });
+        }
+
+        Ok(value.unwrap())
+    }
+    // comma separated values up to the closing token, for array literals and call arguments
+    fn parse_sequence(&mut self, close: Token, open_token: &WrappedToken) -> Result<Vec<Value>, Diagnostic> {
+        let mut values: Vec<Value> = vec![];
+
+        while self.peek_token().is_some_and(|next_token| next_token.token != close) {
+            values.push(self.parse_binary(0)?);
+
+            if self.peek_token().is_some_and(|next_token| next_token.token == Token::Comma) {
+                self.token_idx += 1;
+            } else {
+                break;
+            }
+        }
+
+        self.expect_closing(close, open_token)?;
+        Ok(values)
+    }
+    fn expect_closing(&mut self, close: Token, open_token: &WrappedToken) -> Result<(), Diagnostic> {
+        match self.peek_token() {
+            Some(token) if token.token == close => {
+                self.token_idx += 1;
+                Ok(())
+            }
+            Some(token) => Err(Diagnostic::parser(
+                format!("Expected {}, found {}", close, token.token),
+                token.span,
+            ).with_label(&format!("expected {} here", close))),
+            None => Err(Diagnostic::parser(format!("Missing closing {}", close), open_token.span)
+                .with_label("opened here")),
+        }
+    }
+}
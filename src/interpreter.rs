@@ -1,16 +1,73 @@
+use std::cmp::Ordering;
 use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
 
 use crate::astgen::{ASTToken, Operator, Statement, Value};
+use crate::diagnostic::Diagnostic;
+use crate::methods;
+use crate::tokenizer::Span;
+
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 1000;
+// stack assumed until an embedder sets the real one, std gives spawned threads 2 MiB
+pub const DEFAULT_STACK_SIZE: usize = 2 * 1024 * 1024;
+// native stack kept free when deciding whether an expression may call deeper
+const STACK_HEADROOM_BYTES: usize = 1024 * 1024;
+// how many frames a call depth error lists
+const LISTED_FRAMES: usize = 5;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Type {
-    Integer(u32),
+    Integer(i64),
+    Float(f64),
     String(String),
     Bool(bool),
     Array(Vec<Type>),
     Null,
 }
 
+impl Type {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Type::Integer(_) => "integer",
+            Type::Float(_) => "float",
+            Type::String(_) => "string",
+            Type::Bool(_) => "bool",
+            Type::Array(_) => "array",
+            Type::Null => "null",
+        }
+    }
+    // what `==` says about two values, None when it does not support them
+    pub fn equals(&self, other: &Type) -> Option<bool> {
+        match (self, other) {
+            // null only equals itself, so `readln` hitting the end of input can be told apart from an empty line
+            (Type::Null, _) | (_, Type::Null) => Some(self == other),
+            (Type::Integer(first), Type::Integer(second)) => Some(first == second),
+            (Type::Integer(first), Type::Float(second)) => Some(*first as f64 == *second),
+            (Type::Float(first), Type::Integer(second)) => Some(*first == *second as f64),
+            (Type::Float(first), Type::Float(second)) => Some(first == second),
+            (Type::Bool(first), Type::Bool(second)) => Some(first == second),
+            // against a bool, integers and strings count as their truthiness
+            (Type::Integer(first), Type::Bool(second)) => Some((*first != 0) == *second),
+            (Type::String(first), Type::Bool(second)) => Some(first.is_empty() != *second),
+            (Type::String(first), Type::String(second)) => Some(first == second),
+            _ => None,
+        }
+    }
+    // what `<` and the other orderings say, None when they do not support the values or a float is NaN
+    pub fn compare(&self, other: &Type) -> Option<Ordering> {
+        match (self, other) {
+            (Type::Integer(first), Type::Integer(second)) => Some(first.cmp(second)),
+            (Type::Integer(first), Type::Float(second)) => (*first as f64).partial_cmp(second),
+            (Type::Float(first), Type::Integer(second)) => first.partial_cmp(&(*second as f64)),
+            (Type::Float(first), Type::Float(second)) => first.partial_cmp(second),
+            (Type::Bool(first), Type::Bool(second)) => Some(first.cmp(second)),
+            // strings compare by length in chars
+            (Type::String(first), Type::String(second)) => Some(first.chars().count().cmp(&second.chars().count())),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct WrappedType {
     value: Type,
@@ -22,22 +79,40 @@ impl WrappedType {
         Self { value, addr: None }
     }
     pub fn from_with_addr(value: Type, addr: Option<usize>) -> Self {
-        Self { value, addr: addr }
+        Self { value, addr }
     }
 }
 
+struct CallFrame {
+    name: String,
+    call_span: Span,
+    return_address: usize,
+    // blocks entered in this call that are still open
+    scope_depth: usize,
+    // top level variables, subroutines keep theirs in their call frame
+    variable_map: HashMap<String, usize>,
+    // names declared `global`, these bypass the frame's own variables
+    global_names: Vec<String>,
+}
+
 pub struct Interpreter {
     pub ast_tokens: Vec<ASTToken>,
     pub halted: bool,
+    pub max_call_depth: usize,
+    // size of the native stack the interpreter runs on, 0 turns the stack check off
+    pub stack_size: usize,
+    stack_base: usize,
     inst_ptr: usize,
     memory_cells: Vec<Type>,
     variable_map: HashMap<String, usize>,
     mem_scope_start_stack: Vec<usize>,
     loop_stack: Vec<usize>,
-    else_flag: bool,
-    // return address, scopes deep
-    return_stack: Vec<(usize, usize)>,
+    call_stack: Vec<CallFrame>,
+    // variables hidden by a shadowing one, as (name, hidden addr, shadowing addr)
+    shadowed: Vec<(String, usize, usize)>,
     return_value: Option<Type>,
+    // span of the expression node being evaluated, None between statements
+    node_span: Option<Span>,
 }
 
 impl Interpreter {
@@ -45,14 +120,18 @@ impl Interpreter {
         Self {
             ast_tokens,
             halted: false,
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            stack_size: DEFAULT_STACK_SIZE,
+            stack_base: Interpreter::stack_address(),
             inst_ptr: 0,
             memory_cells: vec![],
             variable_map: HashMap::new(),
             mem_scope_start_stack: vec![0],
             loop_stack: vec![],
-            else_flag: false,
-            return_stack: vec![],
+            call_stack: vec![],
+            shadowed: vec![],
             return_value: None,
+            node_span: None,
         }
     }
     fn current_inst(&self) -> &ASTToken {
@@ -64,137 +143,635 @@ impl Interpreter {
     fn get_inst(&self, idx: usize) -> &ASTToken {
         self.ast_tokens.get(idx).unwrap()
     }
+    #[allow(dead_code)]
     pub fn get_memory(&self) -> &Vec<Type> {
-        return &self.memory_cells;
+        &self.memory_cells
+    }
+    // index of the instruction after the block belonging to the statement at idx
+    fn end_of_body(&self, idx: usize) -> usize {
+        idx + self.get_inst(idx + 1).body_extent.unwrap() + 2
     }
-    fn create_new_variable(&mut self, name: String, value: Type) {
-        if self.variable_map.get(&name) != None {
-            panic!("Trying to allocate a variable '{}' that already exists!", name);
+    // after a branch that did not run, enter the first else whose condition holds
+    fn enter_else_chain(&mut self, mut idx: usize) -> Result<(), Diagnostic> {
+        while self.get_inst(idx).t_type == Statement::Else {
+            // point errors in the condition at this else
+            self.inst_ptr = idx;
+
+            let taken = match self.get_inst(idx).arg1.to_owned() {
+                Some(condition) => {
+                    let condition = self.resolve_argument_value(condition)?;
+                    self.is_truthy(&condition.value)
+                }
+                None => true,
+            };
+
+            if taken {
+                self.inst_ptr = idx + 1;
+                return Ok(());
+            }
+
+            idx = self.end_of_body(idx);
         }
-        self.variable_map.insert(name, self.memory_cells.len());
+
+        self.inst_ptr = idx;
+        Ok(())
+    }
+    // after a branch that ran, skip the rest of the chain
+    fn skip_else_chain(&mut self, mut idx: usize) {
+        while self.get_inst(idx).t_type == Statement::Else {
+            idx = self.end_of_body(idx);
+        }
+
+        self.inst_ptr = idx;
+    }
+    // points at the expression node being evaluated, or the whole statement outside of one
+    fn runtime_error(&self, message: String) -> Diagnostic {
+        Diagnostic::runtime(message, self.node_span.unwrap_or(self.current_inst().span))
+    }
+    fn evaluation_error(&self, message: String) -> Diagnostic {
+        let label = if self.node_span.is_some() { "in this expression" } else { "in this statement" };
+        self.runtime_error(message).with_label(label)
+    }
+    // runs f with errors pointing at span
+    fn at_node<T>(&mut self, span: Span, f: impl FnOnce(&mut Self) -> Result<T, Diagnostic>) -> Result<T, Diagnostic> {
+        let outer_span = self.node_span.replace(span);
+        let result = f(self);
+
+        self.node_span = outer_span;
+        result
+    }
+    fn checked_integer(&self, result: Option<i64>) -> Result<WrappedType, Diagnostic> {
+        match result {
+            Some(value) => Ok(WrappedType::from(Type::Integer(value))),
+            None => Err(self.evaluation_error("Integer overflow".to_string())),
+        }
+    }
+    fn unsupported_operation(&self, operator: &Operator, first: &Type, second: &Type) -> Diagnostic {
+        let message = match second {
+            // accessors have no right hand side
+            Type::Null => format!("Operator {} is not supported on {}", operator, first.type_name()),
+            _ => format!("Operator {} is not supported between {} and {}", operator, first.type_name(), second.type_name()),
+        };
+
+        self.evaluation_error(message)
+    }
+    // where writes to this name go, the innermost frame unless it was declared global there
+    fn variable_map_for(&self, name: &str) -> &HashMap<String, usize> {
+        match self.call_stack.last() {
+            Some(frame) if !frame.global_names.iter().any(|global_name| global_name == name) => &frame.variable_map,
+            _ => &self.variable_map,
+        }
+    }
+    fn variable_map_for_mut(&mut self, name: &str) -> &mut HashMap<String, usize> {
+        match self.call_stack.last_mut() {
+            Some(frame) if !frame.global_names.iter().any(|global_name| global_name == name) => &mut frame.variable_map,
+            _ => &mut self.variable_map,
+        }
+    }
+    fn create_new_variable(&mut self, name: String, value: Type) -> Result<(), Diagnostic> {
+        if self.variable_map_for(&name).get(&name).is_some() {
+            return Err(
+                self.runtime_error(format!("Trying to allocate a variable '{}' that already exists", name))
+                    .with_help("use `set` to change an existing variable")
+            );
+        }
+        let addr = self.memory_cells.len();
+        self.variable_map_for_mut(&name).insert(name, addr);
         self.memory_cells.push(value);
+        Ok(())
     }
-    fn set_or_create_new_variable(&mut self, name: String, value: Type) {
-        let existing_idx = self.variable_map.get(&name);
+    fn shadow_variable(&mut self, name: String, value: Type) {
+        let addr = self.memory_cells.len();
+
+        if let Some(hidden_addr) = self.variable_map_for_mut(&name).insert(name.to_owned(), addr) {
+            self.shadowed.push((name, hidden_addr, addr));
+        }
 
-        if existing_idx != None {
-            self.memory_cells[*existing_idx.unwrap()] = value;
+        self.memory_cells.push(value);
+    }
+    fn set_or_create_new_variable(&mut self, name: String, value: Type) -> Result<(), Diagnostic> {
+        // inside a subroutine this makes a local, even if a global of the same name exists
+        let existing_idx = self.variable_map_for(&name).get(&name).copied();
+
+        if let Some(existing_idx) = existing_idx {
+            self.memory_cells[existing_idx] = value;
+            Ok(())
         } else {
-            self.create_new_variable(name, value);
+            self.create_new_variable(name, value)
         }
     }
-    fn resolve_variable_by_name(&self, name: String) -> WrappedType {
-        let addr = self.variable_map.get(&name);
+    // writes only see the current frame, a global has to be declared `global` before it can be changed
+    fn resolve_variable_for_write(&self, name: String) -> Result<usize, Diagnostic> {
+        if let Some(addr) = self.variable_map_for(&name).get(&name) {
+            return Ok(*addr);
+        }
 
-        if addr == None {
-            panic!("Unknown variable name: {}", name);
+        if !self.call_stack.is_empty() && self.variable_map.contains_key(&name) {
+            return Err(
+                self.runtime_error(format!("Cannot change the global variable '{}' without declaring it global", name))
+                    .with_label("changed here")
+                    .with_help(&format!("add `global {};` to the subroutine first", name))
+            );
         }
 
-        let var = &self.memory_cells[*addr.unwrap()];
-        return WrappedType::from_with_addr(var.to_owned(), Some(addr.unwrap().to_owned()));
+        Err(
+            self.runtime_error(format!("Unknown variable name: {}", name))
+                .with_label("changed here")
+                .with_help(&format!("assign it first with `set {} = ...;`", name))
+        )
     }
-    fn resolve_argument_value(&mut self, argument: Value) -> WrappedType {
-        if let Value::Variable(name) = argument {
-            self.resolve_variable_by_name(name)
-        } else {
-            match argument {
-                Value::IntegerLiteral(value) => WrappedType::from(Type::Integer(value)),
-                Value::StringLiteral(value) => WrappedType::from(Type::String(value)),
-                Value::BoolLiteral(value) => WrappedType::from(Type::Bool(value)),
-                Value::Variable(name) => self.resolve_variable_by_name(name),
-                Value::Expression { values, operators } => {
-                    // oh boy
-                    let mut accumulator: WrappedType = self.resolve_argument_value(
-                        values.first().unwrap().clone()
-                    );
-                    let mut index = 0;
-
-                    for operator in operators {
-                        let second_arg = self.resolve_argument_value(values.get(index + 1).unwrap().clone());
-
-                        accumulator = self.operate_on_types(
-                            accumulator,
-                            second_arg,
-                            operator
-                        );
-                        index += 1;
-                    }
+    fn resolve_variable_by_name(&self, name: String) -> Result<WrappedType, Diagnostic> {
+        // reads fall back to globals when there is no local of that name
+        let addr = match self.variable_map_for(&name).get(&name).or_else(|| self.variable_map.get(&name)) {
+            Some(addr) => *addr,
+            None => return Err(
+                self.runtime_error(format!("Unknown variable name: {}", name))
+                    .with_label("used here")
+                    .with_help(&format!("assign it first with `set {} = ...;`", name))
+            ),
+        };
+
+        let var = &self.memory_cells[addr];
+        Ok(WrappedType::from_with_addr(var.to_owned(), Some(addr)))
+    }
+    fn resolve_argument_value(&mut self, argument: Value) -> Result<WrappedType, Diagnostic> {
+        if self.native_stack_exhausted() {
+            return Err(self.expression_calls_too_deep());
+        }
+
+        match argument {
+            Value::IntegerLiteral(value) => Ok(WrappedType::from(Type::Integer(value))),
+            Value::FloatLiteral(value) => Ok(WrappedType::from(Type::Float(value))),
+            Value::StringLiteral(value) => Ok(WrappedType::from(Type::String(value))),
+            Value::BoolLiteral(value) => Ok(WrappedType::from(Type::Bool(value))),
+            Value::Variable(name, span) => self.at_node(span, |interpreter| interpreter.resolve_variable_by_name(name)),
+            Value::Unary { operator, value, span } => {
+                let operand = self.resolve_argument_value(*value)?;
+
+                self.at_node(span, |interpreter| interpreter.operate_on_type(operand, operator))
+            }
+            // logical operators skip the right hand side when the left decides the result
+            Value::Expression { operator: Operator::And, lhs, rhs, .. } => {
+                let first_arg = self.resolve_argument_value(*lhs)?;
+
+                if !self.is_truthy(&first_arg.value) {
+                    return Ok(WrappedType::from(Type::Bool(false)));
+                }
+
+                let second_arg = self.resolve_argument_value(*rhs)?;
+                Ok(WrappedType::from(Type::Bool(self.is_truthy(&second_arg.value))))
+            }
+            Value::Expression { operator: Operator::Or, lhs, rhs, .. } => {
+                let first_arg = self.resolve_argument_value(*lhs)?;
+
+                if self.is_truthy(&first_arg.value) {
+                    return Ok(WrappedType::from(Type::Bool(true)));
+                }
+
+                let second_arg = self.resolve_argument_value(*rhs)?;
+                Ok(WrappedType::from(Type::Bool(self.is_truthy(&second_arg.value))))
+            }
+            Value::Expression { operator, lhs, rhs, span } => {
+                let first_arg = self.resolve_argument_value(*lhs)?;
+                let second_arg = self.resolve_argument_value(*rhs)?;
+
+                self.at_node(span, |interpreter| interpreter.operate_on_types(first_arg, second_arg, operator))
+            }
+            Value::Call { arguments, subroutine_idx, span, .. } => {
+                Ok(WrappedType::from(self.call_subroutine(subroutine_idx.unwrap(), arguments, span)?))
+            }
+            Value::MethodCall { name, receiver, arguments, span } => self.call_method(name, *receiver, arguments, span),
+            Value::Array(values) => {
+                let mut accumulator: Vec<Type> = vec![];
 
-                    accumulator
+                for value in values {
+                    accumulator.push(self.resolve_argument_value(value)?.value);
                 }
-                Value::Array(values) => {
-                    let mut accumulator: Vec<Type> = vec![];
 
-                    for value in values {
-                        accumulator.push(self.resolve_argument_value(value).value);
+                Ok(WrappedType::from(Type::Array(accumulator)))
+            },
+            Value::Return => {
+                Ok(WrappedType::from(self.return_value.to_owned().unwrap_or(Type::Null)))
+            },
+            Value::Null => Ok(WrappedType::from(Type::Null)),
+        }
+    }
+    // arguments are evaluated in the caller's scope, before the call's frame exists
+    fn evaluate_arguments(&mut self, arguments: Vec<Value>) -> Result<Vec<Type>, Diagnostic> {
+        let mut argument_values: Vec<Type> = vec![];
+
+        for argument in arguments {
+            argument_values.push(self.resolve_argument_value(argument)?.value);
+        }
+
+        Ok(argument_values)
+    }
+    fn enter_subroutine(&mut self, sub_idx: usize, argument_values: Vec<Type>, return_address: usize) -> Result<(), Diagnostic> {
+        // the parser has already checked these line up
+        let parameters = match self.get_inst(sub_idx - 1).arg1.to_owned() {
+            Some(Value::Array(parameters)) => parameters,
+            _ => vec![],
+        };
+        let name = match self.get_inst(sub_idx - 1).arg2.to_owned() {
+            Some(Value::StringLiteral(name)) => name,
+            _ => String::new(),
+        };
+
+        if self.call_stack.len() >= self.max_call_depth {
+            return Err(self.call_depth_exceeded(&name));
+        }
+
+        self.mem_scope_start_stack.push(self.memory_cells.len());
+
+        self.call_stack.push(CallFrame {
+            name,
+            call_span: self.current_inst().span,
+            return_address,
+            scope_depth: 0,
+            variable_map: HashMap::new(),
+            global_names: vec![],
+        });
+
+        for (parameter, argument) in parameters.into_iter().zip(argument_values) {
+            if let Value::Variable(name, _) = parameter {
+                self.create_new_variable(name, argument)?;
+            }
+        }
+
+        self.inst_ptr = sub_idx;
+        Ok(())
+    }
+    // drops every scope the current call still has open
+    fn leave_subroutine(&mut self) -> CallFrame {
+        // invalidate base function scope at least
+        self.invalidate_current_scope();
+
+        for _ in 0..self.call_stack.last().unwrap().scope_depth {
+            // invalidate for every scope remaining in function
+            self.invalidate_current_scope();
+            self.loop_stack.pop();
+        }
+
+        self.call_stack.pop().unwrap()
+    }
+    fn call_depth_exceeded(&self, name: &str) -> Diagnostic {
+        let mut diagnostic = self.runtime_error(format!("Maximum call depth of {} exceeded", self.max_call_depth))
+            .with_label(&format!("calling `{}` here", name));
+
+        // innermost first
+        for frame in self.call_stack.iter().rev().take(LISTED_FRAMES) {
+            diagnostic = diagnostic.with_note(
+                &format!("in `{}`, called from line {}", frame.name, frame.call_span.start.line)
+            );
+        }
+
+        if self.call_stack.len() > LISTED_FRAMES {
+            diagnostic = diagnostic.with_note(&format!("... and {} more frames", self.call_stack.len() - LISTED_FRAMES));
+        }
+
+        diagnostic.with_help("make sure the recursion reaches a base case, or raise the limit with `--max-call-depth`")
+    }
+    // address of a local, its distance from stack_base is the native stack in use
+    #[inline(never)]
+    fn stack_address() -> usize {
+        let marker = 0u8;
+        std::hint::black_box(&marker) as *const u8 as usize
+    }
+    // calls inside expressions recurse natively, this stops them before the stack overflows
+    fn native_stack_exhausted(&self) -> bool {
+        self.stack_size > 0
+            && Interpreter::stack_address().abs_diff(self.stack_base) + STACK_HEADROOM_BYTES > self.stack_size
+    }
+    fn expression_calls_too_deep(&self) -> Diagnostic {
+        self.evaluation_error(format!(
+            "Calls inside expressions are nested too deeply for the interpreter's stack ({} calls deep)", self.call_stack.len()
+        )).with_help(
+            "`call name(...)` statements and `ret name(...)` tail calls do not use the native stack, recurse through those instead"
+        )
+    }
+    // leaves every block up to and including the body of the loop at loop_idx
+    fn unwind_to_loop(&mut self, loop_idx: usize) {
+        loop {
+            let block_idx = self.loop_stack.pop().unwrap();
+            self.invalidate_current_scope();
+
+            if let Some(frame) = self.call_stack.last_mut() {
+                frame.scope_depth -= 1;
+            }
+
+            if block_idx == loop_idx + 1 {
+                break;
+            }
+        }
+    }
+    // runs a call made inside an expression to completion, then resumes at the current statement
+    fn call_subroutine(&mut self, sub_idx: usize, arguments: Vec<Value>, span: Span) -> Result<Type, Diagnostic> {
+        let return_address = self.inst_ptr;
+        let call_depth = self.call_stack.len();
+
+        let argument_values = self.evaluate_arguments(arguments)?;
+        self.at_node(span, |interpreter| interpreter.enter_subroutine(sub_idx, argument_values, return_address))?;
+
+        // statements in the body point at themselves
+        let outer_span = self.node_span.take();
+
+        while self.call_stack.len() > call_depth {
+            self.execute_one()?;
+        }
+
+        self.node_span = outer_span;
+
+        Ok(self.return_value.take().unwrap_or(Type::Null))
+    }
+    fn read_input_line() -> Type {
+        // make sure any pending print output is visible before blocking on input
+        io::stdout().flush().ok();
+        let mut line = String::new();
+
+        match io::stdin().lock().read_line(&mut line) {
+            Ok(0) | Err(_) => {
+                // EOF or unreadable input
+                Type::Null
+            }
+            Ok(_) => {
+                if line.ends_with('\n') {
+                    line.pop();
+
+                    if line.ends_with('\r') {
+                        line.pop();
                     }
+                }
 
-                    WrappedType::from(Type::Array(accumulator))
-                },
-                Value::Return => {
-                    WrappedType::from(self.return_value.to_owned().unwrap())
-                },
-                Value::Null => WrappedType::from(Type::Null),
+                Type::String(line)
             }
         }
     }
     fn invalidate_current_scope(&mut self) {
-        if self.memory_cells.len() > 0 {
-            let invalid_scope_start: usize = self.mem_scope_start_stack.pop().unwrap() - 1;
-            self.variable_map.retain(
-                |_, v| *v <= invalid_scope_start
-            );
-            self.memory_cells.truncate(invalid_scope_start + 1);
+        let invalid_scope_start: usize = self.mem_scope_start_stack.pop().unwrap();
+        let variable_map = match self.call_stack.last_mut() {
+            Some(frame) => &mut frame.variable_map,
+            None => &mut self.variable_map,
+        };
+        variable_map.retain(
+            |_, v| *v < invalid_scope_start
+        );
+        self.memory_cells.truncate(invalid_scope_start);
+
+        // bring back what this scope's variables were hiding
+        while self.shadowed.last().is_some_and(|(_, _, addr)| *addr >= invalid_scope_start) {
+            let (name, hidden_addr, _) = self.shadowed.pop().unwrap();
+            self.variable_map_for_mut(&name).insert(name, hidden_addr);
+        }
+    }
+    // a variable or `name|a||b|`, something that can be written to
+    fn is_element_path(value: &Value) -> bool {
+        match value {
+            Value::Variable(..) => true,
+            Value::Expression { operator: Operator::ArrayAccess, lhs, .. } => Interpreter::is_element_path(lhs),
+            _ => false,
+        }
+    }
+    // the variable's address and the indexes into it, outermost first
+    fn resolve_element_path(&mut self, target: Value) -> Result<(usize, Vec<i64>), Diagnostic> {
+        let mut index_values: Vec<Value> = vec![];
+        let mut base = target;
+
+        while let Value::Expression { operator: Operator::ArrayAccess, lhs, rhs, .. } = base {
+            index_values.push(*rhs);
+            base = *lhs;
+        }
+
+        let (name, name_span) = match base {
+            Value::Variable(name, span) => (name, span),
+            _ => return Err(self.runtime_error("Malformed element assignment".to_string())),
+        };
+        let mut indexes: Vec<i64> = vec![];
+
+        // outermost index first
+        for index_value in index_values.into_iter().rev() {
+            match self.resolve_argument_value(index_value)?.value {
+                Type::Integer(index) => indexes.push(index),
+                other => return Err(
+                    self.evaluation_error(format!("Index must be an integer, found {}", other.type_name()))
+                ),
+            }
+        }
+
+        let addr = self.at_node(name_span, |interpreter| interpreter.resolve_variable_for_write(name))?;
+
+        Ok((addr, indexes))
+    }
+    // `name|a||b| = value`, the stored value is changed in place
+    fn set_element(&mut self, target: Value, value: Type) -> Result<(), Diagnostic> {
+        let (addr, indexes) = self.resolve_element_path(target)?;
+
+        Interpreter::assign_at_index(&mut self.memory_cells[addr], &indexes, value).map_err(
+            |message| self.evaluation_error(message)
+        )
+    }
+    fn element_at(target: &Type, indexes: &[i64]) -> Result<Type, String> {
+        let (index, rest) = match indexes.split_first() {
+            Some((index, rest)) => (*index, rest),
+            None => return Ok(target.to_owned()),
+        };
+
+        match target {
+            Type::Array(elements) => match usize::try_from(index).ok().and_then(|idx| elements.get(idx)) {
+                Some(element) => Interpreter::element_at(element, rest),
+                None => Err(format!("Index {} out of range for array of length {}", index, elements.len())),
+            },
+            Type::String(string) if rest.is_empty() => match usize::try_from(index).ok().and_then(|idx| string.chars().nth(idx)) {
+                Some(char_val) => Ok(Type::String(char_val.to_string())),
+                None => Err(format!("Index {} out of range for string of length {}", index, string.chars().count())),
+            },
+            other => Err(format!("Cannot index into {}", other.type_name())),
+        }
+    }
+    fn assign_at_index(target: &mut Type, indexes: &[i64], value: Type) -> Result<(), String> {
+        if indexes.is_empty() {
+            *target = value;
+            return Ok(());
+        }
+
+        let index = indexes[0];
+
+        match target {
+            Type::Array(elements) => {
+                let length = elements.len();
+                let element = match usize::try_from(index).ok().and_then(|idx| elements.get_mut(idx)) {
+                    Some(element) => element,
+                    None => return Err(format!("Index {} out of range for array of length {}", index, length)),
+                };
+
+                if indexes.len() == 1 {
+                    *element = value;
+                    Ok(())
+                } else {
+                    Interpreter::assign_at_index(element, &indexes[1..], value)
+                }
+            }
+            Type::String(string) if indexes.len() == 1 => {
+                // one char replaces one char, the length never changes
+                let replacement = match value {
+                    Type::String(replacement) if replacement.chars().count() == 1 => replacement.chars().next().unwrap(),
+                    Type::String(replacement) => return Err(
+                        format!("Cannot put a string of length {} into a string element, it must be one char", replacement.chars().count())
+                    ),
+                    other => return Err(format!("Cannot put {} into a string, only strings", other.type_name())),
+                };
+                let mut chars: Vec<char> = string.chars().collect();
+
+                match usize::try_from(index).ok().filter(|idx| *idx < chars.len()) {
+                    Some(idx) => {
+                        chars[idx] = replacement;
+                        *string = chars.into_iter().collect();
+                        Ok(())
+                    }
+                    None => Err(format!("Index {} out of range for string of length {}", index, chars.len())),
+                }
+            }
+            other => Err(format!("Cannot index into {}", other.type_name())),
+        }
+    }
+    // `receiver.name(arguments)`, a changed receiver is written back when it is a variable or element
+    fn call_method(&mut self, name: String, receiver: Value, arguments: Vec<Value>, span: Span) -> Result<WrappedType, Diagnostic> {
+        // arguments go first so `arr.push(arr.pop)` sees the popped array
+        let argument_values = self.evaluate_arguments(arguments)?;
+        let mut written_back: Option<(usize, Vec<i64>)> = None;
+        let mut receiver_value = if methods::mutates(&name) && Interpreter::is_element_path(&receiver) {
+            let (addr, indexes) = self.resolve_element_path(receiver)?;
+            let value = self.at_node(span, |interpreter| {
+                Interpreter::element_at(&interpreter.memory_cells[addr], &indexes).map_err(
+                    |message| interpreter.evaluation_error(message)
+                )
+            })?;
+
+            written_back = Some((addr, indexes));
+            value
+        } else {
+            self.resolve_argument_value(receiver)?.value
+        };
+
+        self.at_node(span, |interpreter| {
+            let method = match methods::find(&receiver_value, &name) {
+                Some(method) => method,
+                None => return Err(
+                    interpreter.evaluation_error(format!("{} has no method `{}`", receiver_value.type_name(), name))
+                ),
+            };
+            let result = (method.call)(&mut receiver_value, &argument_values).map_err(
+                |message| interpreter.evaluation_error(message)
+            )?;
+
+            if let (true, Some((addr, indexes))) = (method.mutates, written_back) {
+                Interpreter::assign_at_index(&mut interpreter.memory_cells[addr], &indexes, receiver_value).map_err(
+                    |message| interpreter.evaluation_error(message)
+                )?;
+            }
+
+            Ok(WrappedType::from(result))
+        })
+    }
+    // empty and zero values are false, everything else is true
+    fn is_truthy(&self, value: &Type) -> bool {
+        match value {
+            Type::Integer(value) => *value != 0,
+            Type::Float(value) => *value != 0.0,
+            Type::String(value) => !value.is_empty(),
+            Type::Bool(value) => *value,
+            Type::Array(value) => !value.is_empty(),
+            Type::Null => false,
+        }
+    }
+    fn operate_on_type(&mut self, operand: WrappedType, operator: Operator) -> Result<WrappedType, Diagnostic> {
+        match (&operand.value, &operator) {
+            (value, Operator::Not) => Ok(WrappedType::from(Type::Bool(!self.is_truthy(value)))),
+            (Type::Integer(value), Operator::Negate) => self.checked_integer(value.checked_neg()),
+            (Type::Float(value), Operator::Negate) => Ok(WrappedType::from(Type::Float(-value))),
+            _ => Err(self.unsupported_operation(&operator, &operand.value, &Type::Null)),
         }
     }
-    fn operate_on_types(&mut self, first: WrappedType, second: WrappedType, operator: Operator) -> WrappedType {
+    // integers are widened, so mixed arithmetic always gives a float
+    fn operate_on_floats(&self, first_val: f64, second_val: f64, operator: Operator, first: &Type, second: &Type) -> Result<WrappedType, Diagnostic> {
+        match operator {
+            // math
+            Operator::Add => { Ok(WrappedType::from(Type::Float(first_val + second_val)))}
+            Operator::Sub => { Ok(WrappedType::from(Type::Float(first_val - second_val)))}
+            Operator::Mul => { Ok(WrappedType::from(Type::Float(first_val * second_val)))}
+            Operator::Div | Operator::Mod if second_val == 0.0 => {
+                Err(self.evaluation_error("Division by zero".to_string()))
+            }
+            Operator::Div => { Ok(WrappedType::from(Type::Float(first_val / second_val)))}
+            Operator::Mod => { Ok(WrappedType::from(Type::Float(first_val % second_val)))}
+            _ => Err(self.unsupported_operation(&operator, first, second))
+        }
+    }
+    fn operate_on_types(&mut self, first: WrappedType, second: WrappedType, operator: Operator) -> Result<WrappedType, Diagnostic> {
+        // comparisons follow the same rules as the array methods, see `Type::equals` and `Type::compare`
+        match operator {
+            Operator::Equals | Operator::NotEquals => {
+                return match first.value.equals(&second.value) {
+                    Some(equal) => Ok(WrappedType::from(Type::Bool(equal == (operator == Operator::Equals)))),
+                    None => Err(self.unsupported_operation(&operator, &first.value, &second.value)),
+                };
+            }
+            Operator::MoreThan | Operator::LessThan | Operator::MoreThanOrEquals | Operator::LessThanOrEquals => {
+                let ordering = match (first.value.compare(&second.value), &first.value, &second.value) {
+                    (Some(ordering), _, _) => ordering,
+                    // NaN is unordered, every comparison with it is false
+                    (None, Type::Integer(_) | Type::Float(_), Type::Integer(_) | Type::Float(_)) => {
+                        return Ok(WrappedType::from(Type::Bool(false)));
+                    }
+                    _ => return Err(self.unsupported_operation(&operator, &first.value, &second.value)),
+                };
+                let result = match operator {
+                    Operator::MoreThan => ordering == Ordering::Greater,
+                    Operator::LessThan => ordering == Ordering::Less,
+                    Operator::MoreThanOrEquals => ordering != Ordering::Less,
+                    _ => ordering != Ordering::Greater,
+                };
+
+                return Ok(WrappedType::from(Type::Bool(result)));
+            }
+            _ => {}
+        }
+
         match &first.value {
-            Type::Bool(first_val) => {
+            Type::Integer(first_val) => {
                 match &second.value {
-                    Type::Bool(second_val) => {
+                    Type::Integer(second_val) => {
                         match operator {
-                            // logical
-                            Operator::Equals => { return WrappedType::from(Type::Bool(first_val == second_val)); }
-                            Operator::NotEquals => { return WrappedType::from(Type::Bool(first_val != second_val)); }
-                            Operator::MoreThan => { return WrappedType::from(Type::Bool(first_val > second_val)); }
-                            Operator::LessThan => { return WrappedType::from(Type::Bool(first_val < second_val)); }
-                            Operator::MoreThanOrEquals => { return WrappedType::from(Type::Bool(first_val >= second_val)); }
-                            Operator::LessThanOrEquals => { return WrappedType::from(Type::Bool(first_val <= second_val)); }
-                            _ => panic!("Invalid operator for comparison statement: {:?}", operator)
+                            // math
+                            Operator::Add => { self.checked_integer(first_val.checked_add(*second_val))}
+                            Operator::Sub => { self.checked_integer(first_val.checked_sub(*second_val))}
+                            Operator::Mul => { self.checked_integer(first_val.checked_mul(*second_val))}
+                            Operator::Div | Operator::Mod if *second_val == 0 => {
+                                Err(self.evaluation_error("Division by zero".to_string()))
+                            }
+                            Operator::Div => { self.checked_integer(first_val.checked_div(*second_val))}
+                            // the remainder always fits, `i64::MIN % -1` is 0
+                            Operator::Mod => { Ok(WrappedType::from(Type::Integer(first_val.wrapping_rem(*second_val))))}
+                            _ => Err(self.unsupported_operation(&operator, &first.value, &second.value))
                         }
                     }
+                    Type::Float(second_val) => {
+                        self.operate_on_floats(*first_val as f64, *second_val, operator, &first.value, &second.value)
+                    }
                     _ => {
-                        panic!("Invalid args for comparison statement: {:?} | {:?}", first.value, second.value);
+                        Err(self.unsupported_operation(&operator, &first.value, &second.value))
                     }
                 }
             }
-            Type::Integer(first_val) => {
+            Type::Float(first_val) => {
                 match &second.value {
                     Type::Integer(second_val) => {
-                        match operator {
-                            // math
-                            Operator::Add => { return WrappedType::from(Type::Integer(first_val + second_val)); }
-                            Operator::Sub => { return WrappedType::from(Type::Integer(first_val - second_val)); }
-                            // logical
-                            Operator::Equals => { return WrappedType::from(Type::Bool(first_val == second_val)); }
-                            Operator::NotEquals => { return WrappedType::from(Type::Bool(first_val != second_val)); }
-                            Operator::MoreThan => { return WrappedType::from(Type::Bool(first_val > second_val)); }
-                            Operator::LessThan => { return WrappedType::from(Type::Bool(first_val < second_val)); }
-                            Operator::MoreThanOrEquals => { return WrappedType::from(Type::Bool(first_val >= second_val)); }
-                            Operator::LessThanOrEquals => { return WrappedType::from(Type::Bool(first_val <= second_val)); }
-                            _ => panic!("Invalid operator for comparison statement: {:?}", operator)
-                        }
+                        self.operate_on_floats(*first_val, *second_val as f64, operator, &first.value, &second.value)
                     }
-                    Type::Bool(second_val) => {
-                        match operator {
-                            // logical
-                            Operator::Equals => { return WrappedType::from(Type::Bool((*first_val == 0) != *second_val)); }
-                            Operator::NotEquals => { return WrappedType::from(Type::Bool((*first_val != 0) != *second_val)); }
-                            _ => panic!("Invalid operator for comparison statement: {:?}", operator)
-                        }
+                    Type::Float(second_val) => {
+                        self.operate_on_floats(*first_val, *second_val, operator, &first.value, &second.value)
                     }
                     _ => {
-                        panic!("Invalid args for comparison statement: {:?} | {:?}", first.value, second.value);
+                        Err(self.unsupported_operation(&operator, &first.value, &second.value))
                     }
                 }
             }
@@ -203,67 +780,53 @@ impl Interpreter {
                     Type::Integer(second_val) => {
                         match operator {
                             // math
-                            Operator::Add => { return WrappedType::from(Type::String(first_val.to_string() + &second_val.to_string())); }
+                            Operator::Add => { Ok(WrappedType::from(Type::String(first_val.to_string() + &second_val.to_string())))}
                             // index access
-                            Operator::ArrayAccess => { return WrappedType::from(Type::String(first_val.chars().nth(*second_val as usize).unwrap().to_string().to_owned())); }
-                            _ => panic!("Invalid operator for comparison statement: {:?}", operator)
+                            Operator::ArrayAccess => {
+                                match usize::try_from(*second_val).ok().and_then(|idx| first_val.chars().nth(idx)) {
+                                    Some(char_val) => { Ok(WrappedType::from(Type::String(char_val.to_string())))}
+                                    None => { Err(self.runtime_error(format!("Index {} out of range for string of length {}", second_val, first_val.chars().count())))}
+                                }
+                            }
+                            _ => Err(self.unsupported_operation(&operator, &first.value, &second.value))
+                        }
+                    }
+                    Type::Float(second_val) => {
+                        match operator {
+                            // math
+                            Operator::Add => { Ok(WrappedType::from(Type::String(first_val.to_string() + &format!("{:?}", second_val))))}
+                            _ => Err(self.unsupported_operation(&operator, &first.value, &second.value))
                         }
                     }
                     Type::Bool(second_val) => {
                         match operator {
                             // math
-                            Operator::Add => { return WrappedType::from(Type::String(first_val.to_string() + &second_val.to_string())); }
-                            // logical
-                            Operator::Equals => { return WrappedType::from(Type::Bool((first_val.len() == 0) != *second_val)); }
-                            Operator::NotEquals => { return WrappedType::from(Type::Bool((first_val.len() != 0) != *second_val)); }
-                            _ => panic!("Invalid operator for comparison statement: {:?}", operator)
+                            Operator::Add => { Ok(WrappedType::from(Type::String(first_val.to_string() + &second_val.to_string())))}
+                            _ => Err(self.unsupported_operation(&operator, &first.value, &second.value))
                         }
                     }
                     Type::String(second_val) => {
                         match operator {
                             // math
-                            Operator::Add => { return WrappedType::from(Type::String(first_val.to_string() + second_val)); }
-                            // logical
-                            Operator::Equals => { return WrappedType::from(Type::Bool(first_val == second_val)); }
-                            Operator::NotEquals => { return WrappedType::from(Type::Bool(first_val != second_val)); }
-                            Operator::MoreThan => { return WrappedType::from(Type::Bool(first_val.len() > second_val.len())); }
-                            Operator::LessThan => { return WrappedType::from(Type::Bool(first_val.len() < second_val.len())); }
-                            Operator::MoreThanOrEquals => { return WrappedType::from(Type::Bool(first_val.len() >= second_val.len())); }
-                            Operator::LessThanOrEquals => { return WrappedType::from(Type::Bool(first_val.len() <= second_val.len())); }
-                            _ => panic!("Invalid operator for comparison statement: {:?}", operator)
+                            Operator::Add => { Ok(WrappedType::from(Type::String(first_val.to_string() + second_val)))}
+                            _ => Err(self.unsupported_operation(&operator, &first.value, &second.value))
                         }
                     }
-                    Type::Null => {
+                    Type::Array(second_val) => {
                         match operator {
-                            Operator::LenAccess => { return WrappedType::from(Type::Integer(first_val.len() as u32)); }
-                            Operator::PopAccess => {
-                                // first val is the String
-                                let ret_var = Type::String(first_val.chars().last().unwrap().to_string().to_owned());
-
-                                if first.addr != None {
-                                    self.memory_cells[first.addr.unwrap()] = Type::String(
-                                        first_val[..(first_val.len() - 1)].to_string()
-                                    );
-                                }
-
-                                return WrappedType::from(ret_var);
-                            }
-                            Operator::PopFrontAccess => {
-                                let ret_var = Type::String(first_val.chars().next().unwrap().to_string().to_owned());
-
-                                if first.addr != None {
-                                    self.memory_cells[first.addr.unwrap()] = Type::String(
-                                        first_val[1..(first_val.len())].to_string()
-                                    );
-                                }
-
-                                return WrappedType::from(ret_var);
-                            }
-                            _ => unreachable!()
+                            // math, formatted the same way print shows it
+                            Operator::Add => { Ok(WrappedType::from(Type::String(first_val.to_string() + &format!("{:?}", second_val))))}
+                            _ => Err(self.unsupported_operation(&operator, &first.value, &second.value))
                         }
                     }
-                    _ => {
-                        panic!("Invalid args for comparison statement: {:?} | {:?}", first.value, second.value);
+                    Type::Null => {
+                        match operator {
+                            // a null operand, accessors never add
+                            Operator::Add => { Ok(WrappedType::from(Type::String(first_val.to_string() + "null")))}
+                            // lengths work in chars, like indexing
+                            Operator::LenAccess => { Ok(WrappedType::from(Type::Integer(first_val.chars().count() as i64)))}
+                            _ => Err(self.unsupported_operation(&operator, &first.value, &second.value))
+                        }
                     }
                 }
             }
@@ -272,78 +835,60 @@ impl Interpreter {
                     Type::Integer(second_val) => {
                         match operator {
                             // math
-                            Operator::Add => { return WrappedType::from(Type::Array([first_val.to_owned(), vec![second.value].to_owned()].concat())); }
+                            Operator::Add => { Ok(WrappedType::from(Type::Array([first_val.to_owned(), vec![second.value].to_owned()].concat())))}
                             // index access
-                            Operator::ArrayAccess => { return WrappedType::from(first_val[*second_val as usize].to_owned()); }
-                            _ => panic!("Invalid operator for comparison statement: {:?}", operator)
+                            Operator::ArrayAccess => {
+                                match usize::try_from(*second_val).ok().and_then(|idx| first_val.get(idx)) {
+                                    Some(element) => { Ok(WrappedType::from(element.to_owned()))}
+                                    None => { Err(self.runtime_error(format!("Index {} out of range for array of length {}", second_val, first_val.len())))}
+                                }
+                            }
+                            _ => Err(self.unsupported_operation(&operator, &first.value, &second.value))
                         }
                     }
-                    Type::Bool(_) => {
+                    Type::Float(_) | Type::Bool(_) => {
                         match operator {
                             // math
-                            Operator::Add => { return WrappedType::from(Type::Array([first_val.to_owned(), vec![second.value].to_owned()].concat())); }
-                            _ => panic!("Invalid operator for comparison statement: {:?}", operator)
+                            Operator::Add => { Ok(WrappedType::from(Type::Array([first_val.to_owned(), vec![second.value].to_owned()].concat())))}
+                            _ => Err(self.unsupported_operation(&operator, &first.value, &second.value))
                         }
                     }
                     Type::String(_) => {
                         match operator {
                             // math
-                            Operator::Add => { return WrappedType::from(Type::Array([first_val.to_owned(), vec![second.value].to_owned()].concat())); }
-                            _ => panic!("Invalid operator for comparison statement: {:?}", operator)
+                            Operator::Add => { Ok(WrappedType::from(Type::Array([first_val.to_owned(), vec![second.value].to_owned()].concat())))}
+                            _ => Err(self.unsupported_operation(&operator, &first.value, &second.value))
                         }
                     }
                     Type::Null => {
                         match operator {
                             // access
-                            Operator::LenAccess => { return WrappedType::from(Type::Integer(first_val.len() as u32)); }
-                            Operator::PopAccess => {
-                                // first val is the Array
-                                let ret_var = first_val.last().unwrap().to_owned();
-
-                                if first.addr != None {
-                                    self.memory_cells[first.addr.unwrap()] = Type::Array(
-                                        first_val[..(first_val.len() - 1)].to_vec()
-                                    );
-                                }
-
-                                return WrappedType::from(ret_var);
-                            }
-                            Operator::PopFrontAccess => {
-                                let ret_var = first_val.first().unwrap().to_owned();
-
-                                if first.addr != None {
-                                    self.memory_cells[first.addr.unwrap()] = Type::Array(
-                                        first_val[1..(first_val.len())].to_vec()
-                                    );
-                                }
-
-                                return WrappedType::from(ret_var);
-                            }
-                            _ => unreachable!()
+                            Operator::LenAccess => { Ok(WrappedType::from(Type::Integer(first_val.len() as i64)))}
+                            _ => Err(self.unsupported_operation(&operator, &first.value, &second.value))
                         }
                     }
                     _ => {
-                        panic!("Invalid args for comparison statement: {:?} | {:?}", first.value, second.value);
+                        Err(self.unsupported_operation(&operator, &first.value, &second.value))
                     }
                 }
             }
             _ => {
-                panic!("Invalid value passed for comparison initialization: {:?}", first.value);
+                Err(self.unsupported_operation(&operator, &first.value, &second.value))
             }
         }
     }
 
-    pub fn execute_one(&mut self) {
+    pub fn execute_one(&mut self) -> Result<(), Diagnostic> {
         let current_instruction = self.current_inst().to_owned();
 
         match current_instruction {
             ASTToken {
-                t_type: Statement::EOF,
+                t_type: Statement::Eof,
                 arg1: _,
                 arg2: _,
                 body_idx: _,
                 body_extent: _,
-                src_line: _,
+                span: _,
             } => {
                 self.halted = true;
             }
@@ -353,14 +898,14 @@ impl Interpreter {
                 arg2: _,
                 body_idx: _,
                 body_extent: _,
-                src_line: _,
+                span: _,
             } => {
                 self.mem_scope_start_stack.push(self.memory_cells.len());
                 self.loop_stack.push(self.inst_ptr);
                 self.inst_ptr += 1;
 
-                if self.return_stack.len() > 0 {
-                    self.return_stack.last_mut().unwrap().1 += 1;
+                if let Some(frame) = self.call_stack.last_mut() {
+                    frame.scope_depth += 1;
                 }
             }
             ASTToken {
@@ -369,44 +914,47 @@ impl Interpreter {
                 arg2: _,
                 body_idx: _,
                 body_extent: _,
-                src_line: _,
+                span: _,
             } => {
                 let loop_idx = self.loop_stack.pop().unwrap() - 1;
                 self.invalidate_current_scope();
 
-                if self.return_stack.len() > 0 {
-                    self.return_stack.last_mut().unwrap().1 -= 1;
+                if let Some(frame) = self.call_stack.last_mut() {
+                    frame.scope_depth -= 1;
                 }
 
                 let previous_token = self.get_inst(loop_idx);
 
-                if loop_idx > 0 {
-                    if let ASTToken {
-                        t_type: Statement::While(_),
-                        arg1: _,
-                        arg2: _,
-                        body_idx: _,
-                        body_extent: _,
-                        src_line: _,
-                    } = previous_token {
-                        self.inst_ptr = loop_idx;
-                    } else {
-                        self.inst_ptr += 1;
-                    }
+                // a block at the very start of the program is owned by the empty root token
+                if let ASTToken {
+                    t_type: Statement::While,
+                    arg1: _,
+                    arg2: _,
+                    body_idx: _,
+                    body_extent: _,
+                    span: _,
+                } = previous_token {
+                    self.inst_ptr = loop_idx;
+                } else {
+                    self.inst_ptr += 1;
                 }
             }
             ASTToken {
                 t_type: Statement::SubroutineCall(sub_idx),
-                arg1: _,
+                arg1,
                 arg2: _,
                 body_idx: _,
                 body_extent: _,
-                src_line: _,
+                span: _,
             } => {
-                self.mem_scope_start_stack.push(self.memory_cells.len());
+                let arguments = match arg1 {
+                    Some(Value::Array(values)) => values,
+                    _ => vec![],
+                };
+
                 // return to token after this call
-                self.return_stack.push((self.inst_ptr + 1, 0));
-                self.inst_ptr = sub_idx.unwrap();
+                let argument_values = self.evaluate_arguments(arguments)?;
+                self.enter_subroutine(sub_idx.unwrap(), argument_values, self.inst_ptr + 1)?;
             }
             ASTToken {
                 t_type: Statement::SubroutineReturn,
@@ -414,18 +962,28 @@ impl Interpreter {
                 arg2: _,
                 body_idx: _,
                 body_extent: _,
-                src_line: _,
+                span: _,
             } => {
-                self.return_value = Some(self.resolve_argument_value(arg1.unwrap()).value);
-                // invalidate base function scope at least
-                self.invalidate_current_scope();
-
-                for _ in 0..self.return_stack.last().unwrap().1 {
-                    // invalidate for every scope remaining in function
-                    self.invalidate_current_scope();
+                // the parser only allows ret inside a sub body
+                if self.call_stack.is_empty() {
+                    return Err(self.runtime_error("`ret` outside of a subroutine call".to_string()).with_label("nothing to return from"));
                 }
 
-                self.inst_ptr = self.return_stack.pop().unwrap().0;
+                match arg1.unwrap() {
+                    Value::Call { arguments, subroutine_idx, span, .. } => {
+                        // a call in tail position takes over this frame, so tail recursion runs in constant memory
+                        let argument_values = self.evaluate_arguments(arguments)?;
+                        let return_address = self.leave_subroutine().return_address;
+
+                        self.at_node(span, |interpreter| {
+                            interpreter.enter_subroutine(subroutine_idx.unwrap(), argument_values, return_address)
+                        })?;
+                    }
+                    value => {
+                        self.return_value = Some(self.resolve_argument_value(value)?.value);
+                        self.inst_ptr = self.leave_subroutine().return_address;
+                    }
+                }
             }
             ASTToken {
                 t_type: Statement::SubroutineDefine,
@@ -433,7 +991,7 @@ impl Interpreter {
                 arg2: _,
                 body_idx: _,
                 body_extent: _,
-                src_line: _,
+                span: _,
             } => {
                 // skip over subroutine when not called
                 self.inst_ptr += self.peek_next_inst().body_extent.unwrap() + 2;
@@ -444,109 +1002,232 @@ impl Interpreter {
                 arg2,
                 body_idx: _,
                 body_extent: _,
-                src_line,
+                span,
             } => {
-                if let Some(Value::Variable(name)) = arg1 {
-                    let second_arg = self.resolve_argument_value(arg2.unwrap());
+                if let Some(Value::Variable(name, _)) = arg1 {
+                    let second_arg = self.resolve_argument_value(arg2.unwrap())?;
 
                     self.create_new_variable(
                         name.to_owned(),
                         second_arg.value,
-                    );
+                    )?;
                 } else {
-                    panic!("Malformed allocate on line {}!", src_line);
+                    return Err(Diagnostic::runtime("Malformed allocate".to_string(), span));
                 }
 
                 self.inst_ptr += 1;
             }
             ASTToken {
-                t_type: Statement::Set,
+                t_type: Statement::Shadow,
                 arg1,
                 arg2,
                 body_idx: _,
                 body_extent: _,
-                src_line,
+                span,
             } => {
-                if let Some(Value::Variable(name)) = arg1 {
-                    let second_arg = self.resolve_argument_value(arg2.unwrap());
+                if let Some(Value::Variable(name, _)) = arg1 {
+                    let second_arg = self.resolve_argument_value(arg2.unwrap())?;
 
-                    self.set_or_create_new_variable(
-                        name.to_owned(),
-                        second_arg.value,
-                    );
+                    self.shadow_variable(name, second_arg.value);
                 } else {
-                    panic!("Malformed set on line {}!", src_line);
+                    return Err(Diagnostic::runtime("Malformed allocate".to_string(), span));
                 }
 
                 self.inst_ptr += 1;
             }
+            ASTToken {
+                t_type: Statement::Set,
+                arg1,
+                arg2,
+                body_idx: _,
+                body_extent: _,
+                span,
+            } => {
+                match arg1 {
+                    Some(Value::Variable(name, _)) => {
+                        let second_arg = self.resolve_argument_value(arg2.unwrap())?;
+
+                        self.set_or_create_new_variable(
+                            name.to_owned(),
+                            second_arg.value,
+                        )?;
+                    }
+                    Some(target @ Value::Expression { operator: Operator::ArrayAccess, .. }) => {
+                        let second_arg = self.resolve_argument_value(arg2.unwrap())?;
+
+                        self.set_element(target, second_arg.value)?;
+                    }
+                    _ => return Err(Diagnostic::runtime("Malformed set".to_string(), span)),
+                }
+
+                self.inst_ptr += 1;
+            }
+            ASTToken {
+                t_type: Statement::Update(operator),
+                arg1,
+                arg2,
+                body_idx: _,
+                body_extent: _,
+                span: _,
+            } => {
+                let (addr, indexes) = self.resolve_element_path(arg1.unwrap())?;
+                let operand = self.resolve_argument_value(arg2.unwrap())?;
+                let current = Interpreter::element_at(&self.memory_cells[addr], &indexes).map_err(
+                    |message| self.evaluation_error(message)
+                )?;
+                let updated = self.operate_on_types(WrappedType::from(current), operand, operator)?;
+
+                Interpreter::assign_at_index(&mut self.memory_cells[addr], &indexes, updated.value).map_err(
+                    |message| self.evaluation_error(message)
+                )?;
+                self.inst_ptr += 1;
+            }
             ASTToken {
                 t_type: Statement::DebugPrintCall,
                 arg1,
                 arg2: _,
                 body_idx: _,
                 body_extent: _,
-                src_line: _,
+                span: _,
             } => {
-                match self.resolve_argument_value(arg1.unwrap()).value {
+                match self.resolve_argument_value(arg1.unwrap())?.value {
                     Type::Integer(value) => print!("{}", value),
-                    Type::String(value) => print!("{}", value.replace("\\n", "\n")), // jank shit
+                    // debug formatting keeps the fraction, so the output parses back to the same float
+                    Type::Float(value) => print!("{:?}", value),
+                    Type::String(value) => print!("{}", value),
                     Type::Bool(value) => print!("{}", value),
                     Type::Array(value) => print!("{:?}", value),
-                    _ => unreachable!(),
+                    Type::Null => print!("null"),
                 }
 
                 self.inst_ptr += 1;
             }
             ASTToken {
-                t_type: Statement::If(comparison_operator),
+                t_type: Statement::ReadLineCall,
                 arg1,
-                arg2,
+                arg2: _,
                 body_idx: _,
                 body_extent: _,
-                src_line: _,
+                span,
             } => {
-                let first_arg: WrappedType = self.resolve_argument_value(arg1.unwrap());
-                let second_arg: WrappedType = self.resolve_argument_value(arg2.unwrap());
+                if let Some(Value::Variable(name, _)) = arg1 {
+                    let input = Interpreter::read_input_line();
 
-                if self.operate_on_types(first_arg, second_arg, comparison_operator).value == Type::Bool(true) {
-                    self.else_flag = false;
-                    self.inst_ptr += 1;
+                    self.set_or_create_new_variable(
+                        name.to_owned(),
+                        input,
+                    )?;
                 } else {
-                    // allow else
-                    self.else_flag = true;
-                    // skip scope open and close at least
-                    self.inst_ptr += self.peek_next_inst().body_extent.unwrap() + 2;
+                    return Err(Diagnostic::runtime("Malformed readln".to_string(), span));
                 }
+
+                self.inst_ptr += 1;
             }
             ASTToken {
-                t_type: Statement::Else,
+                t_type: Statement::Break(loop_idx),
                 arg1: _,
                 arg2: _,
                 body_idx: _,
                 body_extent: _,
-                src_line: _,
+                span: _,
+            } => {
+                self.unwind_to_loop(loop_idx);
+                self.inst_ptr = self.end_of_body(loop_idx);
+            }
+            ASTToken {
+                t_type: Statement::Continue(loop_idx),
+                arg1: _,
+                arg2: _,
+                body_idx: _,
+                body_extent: _,
+                span: _,
+            } => {
+                self.unwind_to_loop(loop_idx);
+                // back to the condition
+                self.inst_ptr = loop_idx;
+            }
+            ASTToken {
+                t_type: Statement::Evaluate,
+                arg1,
+                arg2: _,
+                body_idx: _,
+                body_extent: _,
+                span: _,
+            } => {
+                self.resolve_argument_value(arg1.unwrap())?;
+                self.inst_ptr += 1;
+            }
+            ASTToken {
+                t_type: Statement::Global,
+                arg1,
+                arg2: _,
+                body_idx: _,
+                body_extent: _,
+                span,
+            } => {
+                if let Some(Value::Variable(name, _)) = arg1 {
+                    if !self.variable_map.contains_key(&name) {
+                        return Err(
+                            Diagnostic::runtime(format!("No global variable named '{}'", name), span)
+                                .with_help("assign it at the top level before declaring it global")
+                        );
+                    }
+
+                    // at the top level everything is already global
+                    if let Some(frame) = self.call_stack.last_mut() {
+                        if frame.variable_map.contains_key(&name) {
+                            return Err(
+                                Diagnostic::runtime(format!("'{}' is already a local variable", name), span)
+                                    .with_help("declare it global before assigning to it")
+                            );
+                        }
+
+                        frame.global_names.push(name);
+                    }
+                } else {
+                    return Err(Diagnostic::runtime("Malformed global".to_string(), span));
+                }
+
+                self.inst_ptr += 1;
+            }
+            ASTToken {
+                t_type: Statement::If,
+                arg1,
+                arg2: _,
+                body_idx: _,
+                body_extent: _,
+                span: _,
             } => {
-                if self.else_flag {
-                    self.else_flag = false;
+                let condition: WrappedType = self.resolve_argument_value(arg1.unwrap())?;
+
+                if self.is_truthy(&condition.value) {
                     self.inst_ptr += 1;
                 } else {
-                    // skip scope open and close at least
-                    self.inst_ptr += self.peek_next_inst().body_extent.unwrap() + 2;
+                    self.enter_else_chain(self.end_of_body(self.inst_ptr))?;
                 }
             }
             ASTToken {
-                t_type: Statement::While(comparison_operator),
+                t_type: Statement::Else,
+                arg1: _,
+                arg2: _,
+                body_idx: _,
+                body_extent: _,
+                span: _,
+            } => {
+                // only reached by falling out of a branch that ran
+                self.skip_else_chain(self.inst_ptr);
+            }
+            ASTToken {
+                t_type: Statement::While,
                 arg1,
-                arg2,
+                arg2: _,
                 body_idx: _,
                 body_extent: _,
-                src_line: _,
+                span: _,
             } => {
-                let first_arg: WrappedType = self.resolve_argument_value(arg1.unwrap());
-                let second_arg: WrappedType = self.resolve_argument_value(arg2.unwrap());
+                let condition: WrappedType = self.resolve_argument_value(arg1.unwrap())?;
 
-                if self.operate_on_types(first_arg, second_arg, comparison_operator).value == Type::Bool(true) {
+                if self.is_truthy(&condition.value) {
                     self.inst_ptr += 1;
                 } else {
                     // skip scope open and close at least
@@ -557,5 +1238,7 @@ impl Interpreter {
                 self.inst_ptr += 1;
             }
         }
+
+        Ok(())
     }
 }
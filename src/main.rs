@@ -1,65 +1,159 @@
 use std::env;
 use std::fs::read_to_string;
+use std::thread;
 
 mod astgen;
+mod diagnostic;
+mod expression;
 mod interpreter;
+mod methods;
 mod tokenizer;
 use astgen::ASTGenerator;
-use interpreter::Interpreter;
+use diagnostic::Diagnostic;
+use interpreter::{Interpreter, DEFAULT_MAX_CALL_DEPTH};
 use tokenizer::{Token, Tokenizer, WrappedToken};
 
 fn read_file(filename: &str) -> Vec<String> {
     let mut out_lines: Vec<String> = vec![];
+    let source = match read_to_string(filename) {
+        Ok(source) => source,
+        Err(error) => {
+            eprintln!("error: cannot read {}: {}", filename, error);
+            std::process::exit(1);
+        }
+    };
 
-    for line in read_to_string(filename).unwrap().lines() {
+    for line in source.lines() {
         out_lines.push(line.to_string())
     }
 
-    return out_lines;
+    out_lines
+}
+
+// native stack reserved per level of homulang calls, calls inside expressions recurse in the interpreter,
+// which checks its real stack use so a complex expression fails with a diagnostic instead of overflowing
+const STACK_BYTES_PER_CALL: usize = 32 * 1024;
+const MIN_STACK_BYTES: usize = 8 * 1024 * 1024;
+const MAX_STACK_BYTES: usize = 1024 * 1024 * 1024;
+// every frame costs memory even when it does not recurse natively
+const MAX_CALL_DEPTH: usize = 1_000_000;
+
+fn exit_with_usage(program: &str) -> ! {
+    eprintln!("Usage: {} [--dump-ast] [--max-call-depth <depth>] <filepath>", program);
+    std::process::exit(1);
 }
 
 fn main() {
     let args: Vec<String> = env::args().collect();
-    let filepath: Option<&String>;
-
-    if args.len() < 2 {
-        eprintln!("Usage: {} [--strict] <filepath>", args[0]);
-        std::process::exit(1);
-    } else if args.len() == 2 {
-        // filepath only
-        filepath = Some(&args[1]);
-    } else {
-        eprintln!("Usage: {} [--strict] <filepath>", args[0]);
-        std::process::exit(1);
+    let mut filepath: Option<String> = None;
+    let mut dump_ast = false;
+    let mut max_call_depth = DEFAULT_MAX_CALL_DEPTH;
+    let mut arg_idx = 1;
+
+    while arg_idx < args.len() {
+        match args[arg_idx].as_str() {
+            "--dump-ast" => dump_ast = true,
+            "--max-call-depth" => {
+                arg_idx += 1;
+
+                match args.get(arg_idx).and_then(|depth| depth.parse::<usize>().ok()) {
+                    Some(depth) if (1..=MAX_CALL_DEPTH).contains(&depth) => max_call_depth = depth,
+                    _ => {
+                        eprintln!("--max-call-depth must be between 1 and {}", MAX_CALL_DEPTH);
+                        exit_with_usage(&args[0]);
+                    }
+                }
+            }
+            _ if filepath.is_none() => filepath = Some(args[arg_idx].to_owned()),
+            _ => exit_with_usage(&args[0]),
+        }
+
+        arg_idx += 1;
     }
 
-    let mut tokenizer = Tokenizer::init(read_file(&filepath.unwrap()));
+    let filepath = match filepath {
+        Some(filepath) => filepath,
+        None => exit_with_usage(&args[0]),
+    };
+    let src_lines = read_file(&filepath);
+    let requested_call_depth = max_call_depth;
+
+    let mut stack_size = max_call_depth.saturating_mul(STACK_BYTES_PER_CALL).clamp(MIN_STACK_BYTES, MAX_STACK_BYTES);
+
+    // a memory limit can refuse a large stack, retry with smaller ones and a depth limit to match
+    let handle = loop {
+        let thread_src_lines = src_lines.clone();
+
+        match thread::Builder::new()
+            .stack_size(stack_size)
+            .spawn(move || run(thread_src_lines, dump_ast, max_call_depth, stack_size))
+        {
+            Ok(handle) => break handle,
+            Err(_) if stack_size > MIN_STACK_BYTES => {
+                stack_size = (stack_size / 2).max(MIN_STACK_BYTES);
+                max_call_depth = max_call_depth.min(stack_size / STACK_BYTES_PER_CALL).max(1);
+            }
+            Err(error) => {
+                eprintln!("error: could not start the interpreter with a {} MiB stack: {}", stack_size / (1024 * 1024), error);
+                std::process::exit(1);
+            }
+        }
+    };
+
+    if max_call_depth < requested_call_depth {
+        eprintln!(
+            "warning: only a {} MiB stack could be reserved, the call depth limit is lowered to {}",
+            stack_size / (1024 * 1024), max_call_depth
+        );
+    }
+
+    let result = handle.join();
+
+    match result {
+        Ok(Ok(())) => {}
+        Ok(Err(diagnostic)) => {
+            eprint!("{}", diagnostic.in_file(&filepath).render(&src_lines));
+            std::process::exit(1);
+        }
+        // the panic message has already been printed
+        Err(_) => std::process::exit(101),
+    }
+}
+
+fn run(src_lines: Vec<String>, dump_ast: bool, max_call_depth: usize, stack_size: usize) -> Result<(), Diagnostic> {
+    let mut tokenizer = Tokenizer::init(src_lines);
     let mut raw_tokens: Vec<WrappedToken> = vec![];
-    raw_tokens.push(tokenizer.next_token());
+    raw_tokens.push(tokenizer.next_token()?);
 
-    while raw_tokens.last().unwrap().token != Token::EOF {
-        raw_tokens.push(tokenizer.next_token());
+    while raw_tokens.last().unwrap().token != Token::Eof {
+        raw_tokens.push(tokenizer.next_token()?);
     }
 
     // raw tokens are unusable to the interpreter
-    let unraw_tokens = Tokenizer::post_process(raw_tokens);
+    let unraw_tokens = Tokenizer::post_process(raw_tokens)?;
 
-    for token in &unraw_tokens {
+    for _token in &unraw_tokens {
         //println!("{:?}", token);
     }
 
     let mut astgen = ASTGenerator::init(unraw_tokens);
-    astgen.generate_ast();
+    astgen.generate_ast()?;
 
-    for (index, token) in astgen.generated_ast.iter().enumerate() {
-        println!("{} | {:?}", index, token);
+    if dump_ast {
+        for (index, token) in astgen.generated_ast.iter().enumerate() {
+            println!("{} | {:?}", index, token);
+        }
     }
 
     let mut interpreter = Interpreter::init(astgen.generated_ast.clone());
+    interpreter.max_call_depth = max_call_depth;
+    interpreter.stack_size = stack_size;
 
     while !interpreter.halted {
         //println!("{:?}", interpreter.get_memory());
         //interpreter.print_state();
-        interpreter.execute_one();
+        interpreter.execute_one()?;
     }
+
+    Ok(())
 }
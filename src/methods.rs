@@ -0,0 +1,310 @@
+use crate::diagnostic::{closest_match, Diagnostic};
+use crate::interpreter::Type;
+use crate::tokenizer::Span;
+
+// a built-in called as `receiver.name(arguments)`
+pub struct Method {
+    // the receiver's type, as named by `Type::type_name`
+    pub receiver: &'static str,
+    pub name: &'static str,
+    pub parameters: &'static [&'static str],
+    // the interpreter writes the receiver back when it is a variable or an element of one
+    pub mutates: bool,
+    pub call: fn(&mut Type, &[Type]) -> Result<Type, String>,
+}
+
+// longest string `repeat` builds, larger counts are an error instead of an allocation failure
+const MAX_REPEAT_BYTES: usize = 256 * 1024 * 1024;
+
+pub const METHODS: &[Method] = &[
+    Method { receiver: "array", name: "push", parameters: &["value"], mutates: true, call: array_push },
+    Method { receiver: "array", name: "insert", parameters: &["index", "value"], mutates: true, call: array_insert },
+    Method { receiver: "array", name: "remove", parameters: &["index"], mutates: true, call: array_remove },
+    Method { receiver: "array", name: "contains", parameters: &["value"], mutates: false, call: array_contains },
+    Method { receiver: "array", name: "index_of", parameters: &["value"], mutates: false, call: array_index_of },
+    Method { receiver: "array", name: "reverse", parameters: &[], mutates: true, call: array_reverse },
+    Method { receiver: "array", name: "sort", parameters: &[], mutates: true, call: array_sort },
+    Method { receiver: "array", name: "join", parameters: &["separator"], mutates: false, call: array_join },
+    Method { receiver: "array", name: "slice", parameters: &["start", "end"], mutates: false, call: array_slice },
+    // also written `.pop` and `.popfront` without parentheses, the parser turns those into these calls
+    Method { receiver: "array", name: "pop", parameters: &[], mutates: true, call: array_pop },
+    Method { receiver: "array", name: "popfront", parameters: &[], mutates: true, call: array_popfront },
+    Method { receiver: "string", name: "split", parameters: &["separator"], mutates: false, call: string_split },
+    Method { receiver: "string", name: "trim", parameters: &[], mutates: false, call: string_trim },
+    Method { receiver: "string", name: "upper", parameters: &[], mutates: false, call: string_upper },
+    Method { receiver: "string", name: "lower", parameters: &[], mutates: false, call: string_lower },
+    Method { receiver: "string", name: "replace", parameters: &["from", "to"], mutates: false, call: string_replace },
+    Method { receiver: "string", name: "starts_with", parameters: &["prefix"], mutates: false, call: string_starts_with },
+    Method { receiver: "string", name: "ends_with", parameters: &["suffix"], mutates: false, call: string_ends_with },
+    Method { receiver: "string", name: "find", parameters: &["pattern"], mutates: false, call: string_find },
+    Method { receiver: "string", name: "repeat", parameters: &["count"], mutates: false, call: string_repeat },
+    Method { receiver: "string", name: "chars", parameters: &[], mutates: false, call: string_chars },
+    // pops work in chars, like indexing
+    Method { receiver: "string", name: "pop", parameters: &[], mutates: true, call: string_pop },
+    Method { receiver: "string", name: "popfront", parameters: &[], mutates: true, call: string_popfront },
+];
+
+pub fn find(receiver: &Type, name: &str) -> Option<&'static Method> {
+    METHODS.iter().find(|method| method.receiver == receiver.type_name() && method.name == name)
+}
+
+pub fn mutates(name: &str) -> bool {
+    METHODS.iter().any(|method| method.name == name && method.mutates)
+}
+
+// the receiver's type is only known at runtime, so this only checks the name and argument count
+pub fn check_call(name: &str, argument_count: usize, span: Span) -> Result<(), Diagnostic> {
+    let candidates: Vec<&Method> = METHODS.iter().filter(|method| method.name == name).collect();
+
+    if candidates.is_empty() {
+        let names: Vec<String> = METHODS.iter().map(|method| method.name.to_string()).collect();
+        let diagnostic = Diagnostic::parser(format!("Unknown method `{}`", name), span)
+            .with_label("no method with this name");
+
+        return match closest_match(name, names.iter()) {
+            Some(suggestion) => Err(diagnostic.with_help(&format!("did you mean `{}`?", suggestion))),
+            None => Err(diagnostic),
+        };
+    }
+
+    if !candidates.iter().any(|method| method.parameters.len() == argument_count) {
+        let method = candidates[0];
+
+        return Err(Diagnostic::parser(
+            format!("Method `{}` takes {} argument(s) but {} were given", name, method.parameters.len(), argument_count),
+            span,
+        ).with_label("wrong number of arguments").with_help(
+            &format!("call it as `.{}({})`", name, method.parameters.join(", "))
+        ));
+    }
+
+    Ok(())
+}
+
+fn elements(receiver: &mut Type) -> &mut Vec<Type> {
+    match receiver {
+        Type::Array(elements) => elements,
+        _ => unreachable!(),
+    }
+}
+
+fn string(receiver: &Type) -> &str {
+    match receiver {
+        Type::String(string) => string,
+        _ => unreachable!(),
+    }
+}
+
+fn string_argument<'a>(argument: &'a Type, parameter: &str) -> Result<&'a str, String> {
+    match argument {
+        Type::String(string) => Ok(string),
+        other => Err(format!("{} must be a string, found {}", parameter, other.type_name())),
+    }
+}
+
+// `allow_end` also accepts the length itself, for inserting at the end and slice bounds
+fn index_argument(argument: &Type, length: usize, allow_end: bool, container: &str) -> Result<usize, String> {
+    let index = match argument {
+        Type::Integer(index) => *index,
+        other => return Err(format!("Index must be an integer, found {}", other.type_name())),
+    };
+
+    match usize::try_from(index).ok().filter(|idx| *idx < length || (allow_end && *idx == length)) {
+        Some(idx) => Ok(idx),
+        None => Err(format!("Index {} out of range for {} of length {}", index, container, length)),
+    }
+}
+
+fn array_push(receiver: &mut Type, arguments: &[Type]) -> Result<Type, String> {
+    elements(receiver).push(arguments[0].to_owned());
+    Ok(Type::Null)
+}
+
+fn array_insert(receiver: &mut Type, arguments: &[Type]) -> Result<Type, String> {
+    let elements = elements(receiver);
+    let idx = index_argument(&arguments[0], elements.len(), true, "array")?;
+
+    elements.insert(idx, arguments[1].to_owned());
+    Ok(Type::Null)
+}
+
+fn array_remove(receiver: &mut Type, arguments: &[Type]) -> Result<Type, String> {
+    let elements = elements(receiver);
+    let idx = index_argument(&arguments[0], elements.len(), false, "array")?;
+
+    Ok(elements.remove(idx))
+}
+
+fn array_contains(receiver: &mut Type, arguments: &[Type]) -> Result<Type, String> {
+    // the same equality as `==`
+    Ok(Type::Bool(elements(receiver).iter().any(|element| element.equals(&arguments[0]) == Some(true))))
+}
+
+// -1 when the value is not in the array
+fn array_index_of(receiver: &mut Type, arguments: &[Type]) -> Result<Type, String> {
+    match elements(receiver).iter().position(|element| element.equals(&arguments[0]) == Some(true)) {
+        Some(idx) => Ok(Type::Integer(idx as i64)),
+        None => Ok(Type::Integer(-1)),
+    }
+}
+
+fn array_reverse(receiver: &mut Type, _: &[Type]) -> Result<Type, String> {
+    elements(receiver).reverse();
+    Ok(receiver.to_owned())
+}
+
+fn array_sort(receiver: &mut Type, _: &[Type]) -> Result<Type, String> {
+    let elements = elements(receiver);
+
+    // ordered like `<`, neighbours being comparable means every pair is, so the sort itself cannot fail
+    for pair in elements.windows(2) {
+        if pair[0].compare(&pair[1]).is_none() {
+            return Err(format!("Cannot sort an array containing {} and {}", pair[0].type_name(), pair[1].type_name()));
+        }
+    }
+
+    elements.sort_by(|first, second| first.compare(second).unwrap());
+    Ok(receiver.to_owned())
+}
+
+fn array_join(receiver: &mut Type, arguments: &[Type]) -> Result<Type, String> {
+    let separator = match &arguments[0] {
+        Type::String(separator) => separator,
+        other => return Err(format!("Separator must be a string, found {}", other.type_name())),
+    };
+    let mut parts: Vec<String> = vec![];
+
+    for element in elements(receiver).iter() {
+        parts.push(match element {
+            Type::String(value) => value.to_owned(),
+            Type::Integer(value) => value.to_string(),
+            Type::Float(value) => format!("{:?}", value),
+            Type::Bool(value) => value.to_string(),
+            other => return Err(format!("Cannot join an array containing {}", other.type_name())),
+        });
+    }
+
+    Ok(Type::String(parts.join(separator)))
+}
+
+// elements from start up to but not including end
+fn array_slice(receiver: &mut Type, arguments: &[Type]) -> Result<Type, String> {
+    let elements = elements(receiver);
+    let start = index_argument(&arguments[0], elements.len(), true, "array")?;
+    let end = index_argument(&arguments[1], elements.len(), true, "array")?;
+
+    if start > end {
+        return Err(format!("Slice start {} is after its end {}", start, end));
+    }
+
+    Ok(Type::Array(elements[start..end].to_vec()))
+}
+
+fn array_pop(receiver: &mut Type, _: &[Type]) -> Result<Type, String> {
+    elements(receiver).pop().ok_or("Cannot pop from an empty array".to_string())
+}
+
+fn array_popfront(receiver: &mut Type, _: &[Type]) -> Result<Type, String> {
+    let elements = elements(receiver);
+
+    if elements.is_empty() {
+        return Err("Cannot pop from an empty array".to_string());
+    }
+
+    Ok(elements.remove(0))
+}
+
+fn string_split(receiver: &mut Type, arguments: &[Type]) -> Result<Type, String> {
+    let separator = string_argument(&arguments[0], "Separator")?;
+
+    if separator.is_empty() {
+        return Err("Separator cannot be empty, use `.chars()` to split into characters".to_string());
+    }
+
+    Ok(Type::Array(string(receiver).split(separator).map(|part| Type::String(part.to_string())).collect()))
+}
+
+fn string_trim(receiver: &mut Type, _: &[Type]) -> Result<Type, String> {
+    Ok(Type::String(string(receiver).trim().to_string()))
+}
+
+fn string_upper(receiver: &mut Type, _: &[Type]) -> Result<Type, String> {
+    Ok(Type::String(string(receiver).to_uppercase()))
+}
+
+fn string_lower(receiver: &mut Type, _: &[Type]) -> Result<Type, String> {
+    Ok(Type::String(string(receiver).to_lowercase()))
+}
+
+// every occurrence is replaced
+fn string_replace(receiver: &mut Type, arguments: &[Type]) -> Result<Type, String> {
+    let from = string_argument(&arguments[0], "Pattern")?;
+    let to = string_argument(&arguments[1], "Replacement")?;
+
+    if from.is_empty() {
+        return Err("Pattern to replace cannot be empty".to_string());
+    }
+
+    Ok(Type::String(string(receiver).replace(from, to)))
+}
+
+fn string_starts_with(receiver: &mut Type, arguments: &[Type]) -> Result<Type, String> {
+    Ok(Type::Bool(string(receiver).starts_with(string_argument(&arguments[0], "Prefix")?)))
+}
+
+fn string_ends_with(receiver: &mut Type, arguments: &[Type]) -> Result<Type, String> {
+    Ok(Type::Bool(string(receiver).ends_with(string_argument(&arguments[0], "Suffix")?)))
+}
+
+// character index of the first occurrence, -1 when there is none
+fn string_find(receiver: &mut Type, arguments: &[Type]) -> Result<Type, String> {
+    let string = string(receiver);
+
+    match string.find(string_argument(&arguments[0], "Pattern")?) {
+        Some(byte_idx) => Ok(Type::Integer(string[..byte_idx].chars().count() as i64)),
+        None => Ok(Type::Integer(-1)),
+    }
+}
+
+fn string_repeat(receiver: &mut Type, arguments: &[Type]) -> Result<Type, String> {
+    let string = string(receiver);
+    let count = match &arguments[0] {
+        Type::Integer(count) if *count >= 0 => *count,
+        Type::Integer(count) => return Err(format!("Cannot repeat a string {} times", count)),
+        other => return Err(format!("Count must be an integer, found {}", other.type_name())),
+    };
+
+    match usize::try_from(count).ok().and_then(|count| string.len().checked_mul(count)) {
+        Some(length) if length <= MAX_REPEAT_BYTES => Ok(Type::String(string.repeat(count as usize))),
+        _ => Err(format!(
+            "Repeating a string of {} bytes {} times is too long, the limit is {} bytes", string.len(), count, MAX_REPEAT_BYTES
+        )),
+    }
+}
+
+fn string_chars(receiver: &mut Type, _: &[Type]) -> Result<Type, String> {
+    Ok(Type::Array(string(receiver).chars().map(|char_val| Type::String(char_val.to_string())).collect()))
+}
+
+fn string_pop(receiver: &mut Type, _: &[Type]) -> Result<Type, String> {
+    match receiver {
+        Type::String(string) => match string.pop() {
+            Some(char_val) => Ok(Type::String(char_val.to_string())),
+            None => Err("Cannot pop from an empty string".to_string()),
+        },
+        _ => unreachable!(),
+    }
+}
+
+fn string_popfront(receiver: &mut Type, _: &[Type]) -> Result<Type, String> {
+    match receiver {
+        Type::String(string) => match string.chars().next() {
+            Some(char_val) => {
+                string.remove(0);
+                Ok(Type::String(char_val.to_string()))
+            }
+            None => Err("Cannot pop from an empty string".to_string()),
+        },
+        _ => unreachable!(),
+    }
+}
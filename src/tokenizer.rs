@@ -1,16 +1,36 @@
-#[derive(Debug, PartialEq, Eq, Clone)]
+use std::fmt;
+
+use crate::diagnostic::Diagnostic;
+
+// a piece of an interpolated string literal
+#[derive(Debug, PartialEq, Clone)]
+pub enum StringPart {
+    Literal(String),
+    // the post processed tokens between `{` and `}`
+    Expression(Vec<WrappedToken>),
+}
+
+#[derive(Debug, PartialEq, Clone)]
 pub enum Token {
     RawIdentifier(String),
-    IntegerLiteral(u32),
+    IntegerLiteral(i64),
+    FloatLiteral(f64),
     StringLiteral(String),
+    // a string literal containing `{expression}`s
+    InterpolatedString(Vec<StringPart>),
     Symbol(char),
     Whitespace,
     Comment,
-    EOF,
+    Eof,
     // special tokens, returned by post_process
     If,
     While,
     Else,
+    Elif,
+    For,
+    In,
+    Break,
+    Continue,
     ScopeOpen,
     ScopeClose,
     ParensOpen,
@@ -27,19 +47,32 @@ pub enum Token {
     LessThan,
     MoreThanOrEquals,
     LessThanOrEquals,
+    And,
+    Or,
+    Not,
     BoolTrue,
     BoolFalse,
+    Null,
     Plus,
     Minus,
+    Multiply,
+    Divide,
+    Modulo,
     PlusEquals,
     MinusEquals,
+    MultiplyEquals,
+    DivideEquals,
+    ModuloEquals,
     Alloc,
     Set,
     ArrayOpen,
     ArrayClose,
     Comma,
+    Colon,
+    Range,
     Print,
     ReadLine,
+    Global,
     LineEnd,
     Variable(String),
     LenAccess,
@@ -47,38 +80,172 @@ pub enum Token {
     PopFrontAccess,
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+impl fmt::Display for Token {
+    // how the token looks in source, for error messages
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::RawIdentifier(value) => write!(f, "`{}`", value),
+            Token::Variable(value) => write!(f, "`{}`", value),
+            Token::IntegerLiteral(value) => write!(f, "`{}`", value),
+            Token::FloatLiteral(value) => write!(f, "`{:?}`", value),
+            Token::StringLiteral(value) => write!(f, "`\"{}\"`", value),
+            Token::InterpolatedString(_) => write!(f, "interpolated string"),
+            Token::Symbol(value) => write!(f, "`{}`", value),
+            Token::Whitespace => write!(f, "whitespace"),
+            Token::Comment => write!(f, "comment"),
+            Token::Eof => write!(f, "end of file"),
+            Token::If => write!(f, "`if`"),
+            Token::While => write!(f, "`while`"),
+            Token::Else => write!(f, "`else`"),
+            Token::Elif => write!(f, "`elif`"),
+            Token::For => write!(f, "`for`"),
+            Token::In => write!(f, "`in`"),
+            Token::Break => write!(f, "`break`"),
+            Token::Continue => write!(f, "`continue`"),
+            Token::ScopeOpen => write!(f, "`{{`"),
+            Token::ScopeClose => write!(f, "`}}`"),
+            Token::ParensOpen => write!(f, "`(`"),
+            Token::ParensClose => write!(f, "`)`"),
+            Token::Assign => write!(f, "`=`"),
+            Token::ArrayAccess => write!(f, "`|`"),
+            Token::SubroutineCall => write!(f, "`call`"),
+            Token::SubroutineDirect => write!(f, "`->`"),
+            Token::SubroutineReturn => write!(f, "`ret`"),
+            Token::SubroutineDefine => write!(f, "`sub`"),
+            Token::Equals => write!(f, "`==`"),
+            Token::NotEquals => write!(f, "`!=`"),
+            Token::MoreThan => write!(f, "`>`"),
+            Token::LessThan => write!(f, "`<`"),
+            Token::MoreThanOrEquals => write!(f, "`>=`"),
+            Token::LessThanOrEquals => write!(f, "`<=`"),
+            Token::And => write!(f, "`&&`"),
+            Token::Or => write!(f, "`||`"),
+            Token::Not => write!(f, "`!`"),
+            Token::BoolTrue => write!(f, "`true`"),
+            Token::BoolFalse => write!(f, "`false`"),
+            Token::Null => write!(f, "`null`"),
+            Token::Plus => write!(f, "`+`"),
+            Token::Minus => write!(f, "`-`"),
+            Token::Multiply => write!(f, "`*`"),
+            Token::Divide => write!(f, "`/`"),
+            Token::Modulo => write!(f, "`%`"),
+            Token::PlusEquals => write!(f, "`+=`"),
+            Token::MinusEquals => write!(f, "`-=`"),
+            Token::MultiplyEquals => write!(f, "`*=`"),
+            Token::DivideEquals => write!(f, "`/=`"),
+            Token::ModuloEquals => write!(f, "`%=`"),
+            Token::Alloc => write!(f, "`alloc`"),
+            Token::Set => write!(f, "`set`"),
+            Token::ArrayOpen => write!(f, "`[`"),
+            Token::ArrayClose => write!(f, "`]`"),
+            Token::Comma => write!(f, "`,`"),
+            Token::Colon => write!(f, "`:`"),
+            Token::Range => write!(f, "`..`"),
+            Token::Print => write!(f, "`print`"),
+            Token::ReadLine => write!(f, "`readln`"),
+            Token::Global => write!(f, "`global`"),
+            Token::LineEnd => write!(f, "`;`"),
+            Token::LenAccess => write!(f, "`.`"),
+            Token::PopAccess => write!(f, "`.pop`"),
+            Token::PopFrontAccess => write!(f, "`.popfront`"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct Position {
+    // 1-based line and column (in chars), 0-based byte offset into the source
+    pub line: usize,
+    pub column: usize,
+    pub offset: usize,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct Span {
+    pub start: Position,
+    // exclusive, points just past the last char
+    pub end: Position,
+}
+
+impl Span {
+    pub fn new(start: Position, end: Position) -> Self {
+        Self { start, end }
+    }
+    // span covering both self and other
+    pub fn to(&self, other: Span) -> Span {
+        let start = if other.start.offset < self.start.offset { other.start } else { self.start };
+        let end = if other.end.offset > self.end.offset { other.end } else { self.end };
+        Span { start, end }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
 pub struct WrappedToken {
     pub token: Token,
-    pub src_line: usize,
+    pub span: Span,
 }
 
 impl WrappedToken {
-    pub fn from(token: Token) -> Self {
-        Self { token, src_line: 0 }
-    }
-    pub fn from_with_line(token: Token, src_line: usize) -> Self {
-        Self { token, src_line }
+    pub fn from_with_span(token: Token, span: Span) -> Self {
+        Self { token, span }
     }
 }
 
 pub struct Tokenizer {
     lines: Vec<String>,
+    // byte offset of the start of every line
+    line_offsets: Vec<usize>,
     line_idx: usize,
     char_idx: usize,
 }
 
 impl Tokenizer {
-    pub fn init(lines: Vec<String>) -> Self { Self { lines, line_idx: 0, char_idx: 0 } }
+    pub fn init(lines: Vec<String>) -> Self {
+        let mut line_offsets: Vec<usize> = vec![];
+        let mut offset = 0;
+
+        for line in &lines {
+            line_offsets.push(offset);
+            offset += line.len() + 1;
+        }
+
+        Self { lines, line_offsets, line_idx: 0, char_idx: 0 }
+    }
+    fn position(&self) -> Position {
+        if !self.line_idx_in_bounds() {
+            // past the last line, point at the end of the source
+            let last_line = self.lines.last().map_or("", |line| line.as_str());
+
+            return Position {
+                line: self.lines.len().max(1),
+                column: last_line.chars().count() + 1,
+                offset: self.line_offsets.last().map_or(0, |offset| offset + last_line.len()),
+            };
+        }
+
+        let line = self.get_current_line();
+        let byte_idx = line.char_indices().nth(self.char_idx).map_or(line.len(), |(byte_idx, _)| byte_idx);
+
+        Position {
+            line: self.line_idx + 1,
+            column: self.char_idx + 1,
+            offset: self.line_offsets[self.line_idx] + byte_idx,
+        }
+    }
+    fn span_from(&self, start: Position) -> Span {
+        Span::new(start, self.position())
+    }
     fn line_idx_in_bounds(&self) -> bool { self.line_idx < self.lines.len() }
-    fn char_idx_in_bounds(&self) -> bool { self.char_idx < self.get_current_line().len() }
+    // false past the last line too, an empty source has no lines at all
+    fn char_idx_in_bounds(&self) -> bool { self.line_idx_in_bounds() && self.char_idx < self.get_current_line().chars().count() }
     fn get_current_line(&self) -> &String { &self.lines[self.line_idx] }
-    fn get_current_char(&self) -> char { self.get_current_line().chars().collect::<Vec<char>>()[self.char_idx] }
+    fn get_current_char(&self) -> char { self.get_current_line().chars().nth(self.char_idx).unwrap() }
+    fn peek_char(&self, offset: usize) -> Option<char> { self.get_current_line().chars().nth(self.char_idx + offset) }
     fn special_symbols() -> Vec<char> {
-        vec!['!', '?', '=', '{', '}', '>', '<', ';', '+', '-', '[', ']', '|', '(', ')', '.', ',']
+        vec!['!', '?', '=', '{', '}', '>', '<', ';', '+', '-', '*', '/', '%', '[', ']', '|', '&', '(', ')', '.', ',', ':']
     }
 
-    pub fn next_token(&mut self) -> WrappedToken {
+    pub fn next_token(&mut self) -> Result<WrappedToken, Diagnostic> {
         if !self.char_idx_in_bounds() {
             self.char_idx = 0;
             self.line_idx += 1;
@@ -88,29 +255,38 @@ impl Tokenizer {
             }
         }
         if !self.line_idx_in_bounds() {
-            return WrappedToken::from(Token::EOF);
+            return Ok(WrappedToken::from_with_span(Token::Eof, self.span_from(self.position())));
         }
 
         let this_char = self.get_current_char();
+        let start = self.position();
 
         if this_char.is_ascii_digit() {
-            return self.consume_integer();
+            self.consume_number(start)
+        } else if this_char == 'r' && self.peek_char(1) == Some('"') {
+            // raw string, escapes are kept as written
+            self.char_idx += 1;
+            self.consume_string_literal(start, true)
         } else if this_char.is_ascii_alphabetic() || this_char == '_' {
             // identifiers can only start with a letter
-            return self.consume_identifier();
+            Ok(self.consume_identifier(start))
         } else if this_char == '"' {
-            return self.consume_string_literal();
+            self.consume_string_literal(start, false)
         } else if this_char.is_ascii_whitespace() {
             // coalesce whitespace
-            return self.consume_whitespace();
+            Ok(self.consume_whitespace(start))
         } else if this_char == '#' {
             // comments
-            return self.consume_comment();
+            Ok(self.consume_comment(start))
         } else if Tokenizer::special_symbols().contains(&this_char) {
             self.char_idx += 1;
-            return WrappedToken::from_with_line(Token::Symbol(this_char), self.line_idx + 1);
+            Ok(WrappedToken::from_with_span(Token::Symbol(this_char), self.span_from(start)))
         } else {
-            panic!("Unknown char '{}' at line {}, exiting.", this_char, self.line_idx + 1)
+            self.char_idx += 1;
+            Err(
+                Diagnostic::tokenizer(format!("Unknown char '{}'", this_char), self.span_from(start))
+                    .with_label("not valid in homulang source")
+            )
         }
     }
 
@@ -118,38 +294,50 @@ impl Tokenizer {
         match &token.token {
             Token::RawIdentifier(value) => {
                 match value.as_str() {
-                    "if" => WrappedToken::from_with_line(Token::If, token.src_line),
-                    "while" => WrappedToken::from_with_line(Token::While, token.src_line),
-                    "else" => WrappedToken::from_with_line(Token::Else, token.src_line),
-                    "alloc" => WrappedToken::from_with_line(Token::Alloc, token.src_line),
-                    "set" => WrappedToken::from_with_line(Token::Set, token.src_line),
-                    "print" => WrappedToken::from_with_line(Token::Print, token.src_line),
-                    "readln" => WrappedToken::from_with_line(Token::ReadLine, token.src_line),
-                    "true" => WrappedToken::from_with_line(Token::BoolTrue, token.src_line),
-                    "false" => WrappedToken::from_with_line(Token::BoolFalse, token.src_line),
-                    "call" => WrappedToken::from_with_line(Token::SubroutineCall, token.src_line),
-                    "ret" => WrappedToken::from_with_line(Token::SubroutineReturn, token.src_line),
-                    "sub" => WrappedToken::from_with_line(Token::SubroutineDefine, token.src_line),
-                    _ => WrappedToken::from_with_line(Token::Variable(value.to_string()), token.src_line),
+                    "if" => WrappedToken::from_with_span(Token::If, token.span),
+                    "while" => WrappedToken::from_with_span(Token::While, token.span),
+                    "else" => WrappedToken::from_with_span(Token::Else, token.span),
+                    "elif" => WrappedToken::from_with_span(Token::Elif, token.span),
+                    "for" => WrappedToken::from_with_span(Token::For, token.span),
+                    "in" => WrappedToken::from_with_span(Token::In, token.span),
+                    "break" => WrappedToken::from_with_span(Token::Break, token.span),
+                    "continue" => WrappedToken::from_with_span(Token::Continue, token.span),
+                    "alloc" => WrappedToken::from_with_span(Token::Alloc, token.span),
+                    "set" => WrappedToken::from_with_span(Token::Set, token.span),
+                    "print" => WrappedToken::from_with_span(Token::Print, token.span),
+                    "readln" => WrappedToken::from_with_span(Token::ReadLine, token.span),
+                    "global" => WrappedToken::from_with_span(Token::Global, token.span),
+                    "true" => WrappedToken::from_with_span(Token::BoolTrue, token.span),
+                    "false" => WrappedToken::from_with_span(Token::BoolFalse, token.span),
+                    "null" => WrappedToken::from_with_span(Token::Null, token.span),
+                    "call" => WrappedToken::from_with_span(Token::SubroutineCall, token.span),
+                    "ret" => WrappedToken::from_with_span(Token::SubroutineReturn, token.span),
+                    "sub" => WrappedToken::from_with_span(Token::SubroutineDefine, token.span),
+                    _ => WrappedToken::from_with_span(Token::Variable(value.to_string()), token.span),
                 }
             }
             Token::Symbol(value) => {
                 match value {
-                    '=' => WrappedToken::from_with_line(Token::Assign, token.src_line),
-                    '{' => WrappedToken::from_with_line(Token::ScopeOpen, token.src_line),
-                    '}' => WrappedToken::from_with_line(Token::ScopeClose, token.src_line),
-                    '>' => WrappedToken::from_with_line(Token::MoreThan, token.src_line),
-                    '<' => WrappedToken::from_with_line(Token::LessThan, token.src_line),
-                    ';' => WrappedToken::from_with_line(Token::LineEnd, token.src_line),
-                    '+' => WrappedToken::from_with_line(Token::Plus, token.src_line),
-                    '-' => WrappedToken::from_with_line(Token::Minus, token.src_line),
-                    '[' => WrappedToken::from_with_line(Token::ArrayOpen, token.src_line),
-                    ']' => WrappedToken::from_with_line(Token::ArrayClose, token.src_line),
-                    '|' => WrappedToken::from_with_line(Token::ArrayAccess, token.src_line),
-                    '.' => WrappedToken::from_with_line(Token::LenAccess, token.src_line),
-                    '(' => WrappedToken::from_with_line(Token::ParensOpen, token.src_line),
-                    ')' => WrappedToken::from_with_line(Token::ParensClose, token.src_line),
-                    ',' => WrappedToken::from_with_line(Token::Comma, token.src_line),
+                    '=' => WrappedToken::from_with_span(Token::Assign, token.span),
+                    '{' => WrappedToken::from_with_span(Token::ScopeOpen, token.span),
+                    '}' => WrappedToken::from_with_span(Token::ScopeClose, token.span),
+                    '>' => WrappedToken::from_with_span(Token::MoreThan, token.span),
+                    '<' => WrappedToken::from_with_span(Token::LessThan, token.span),
+                    '!' => WrappedToken::from_with_span(Token::Not, token.span),
+                    ';' => WrappedToken::from_with_span(Token::LineEnd, token.span),
+                    '+' => WrappedToken::from_with_span(Token::Plus, token.span),
+                    '-' => WrappedToken::from_with_span(Token::Minus, token.span),
+                    '*' => WrappedToken::from_with_span(Token::Multiply, token.span),
+                    '/' => WrappedToken::from_with_span(Token::Divide, token.span),
+                    '%' => WrappedToken::from_with_span(Token::Modulo, token.span),
+                    '[' => WrappedToken::from_with_span(Token::ArrayOpen, token.span),
+                    ']' => WrappedToken::from_with_span(Token::ArrayClose, token.span),
+                    '|' => WrappedToken::from_with_span(Token::ArrayAccess, token.span),
+                    '.' => WrappedToken::from_with_span(Token::LenAccess, token.span),
+                    '(' => WrappedToken::from_with_span(Token::ParensOpen, token.span),
+                    ')' => WrappedToken::from_with_span(Token::ParensClose, token.span),
+                    ',' => WrappedToken::from_with_span(Token::Comma, token.span),
+                    ':' => WrappedToken::from_with_span(Token::Colon, token.span),
                     _ => token,
                 }
             }
@@ -157,7 +345,20 @@ impl Tokenizer {
         }
     }
 
-    pub fn post_process(tokens: Vec<WrappedToken>) -> Vec<WrappedToken> {
+    fn coalesce_with_previous(out_tokens: &mut Vec<WrappedToken>, token: Token, last_part: &WrappedToken) {
+        // replace the previous token, the coalesced token spans both
+        let previous = out_tokens.pop().unwrap();
+        out_tokens.push(WrappedToken::from_with_span(token, previous.span.to(last_part.span)));
+    }
+
+    // whether the next token that is not whitespace opens parentheses
+    fn parens_follow(tokens: &[WrappedToken], token_idx: usize) -> bool {
+        tokens[token_idx + 1..].iter().find(|token| token.token != Token::Whitespace).is_some_and(
+            |token| token.token == Token::Symbol('(')
+        )
+    }
+
+    pub fn post_process(tokens: Vec<WrappedToken>) -> Result<Vec<WrappedToken>, Diagnostic> {
         let mut out_tokens: Vec<WrappedToken> = vec![];
 
         // remove whitespace and coalesce some tokens
@@ -177,29 +378,65 @@ impl Tokenizer {
                         match &tokens[token_idx - 1].token { // get and replace previous token
                             // comparison
                             Token::Symbol('=') => {
-                                out_tokens.truncate(out_tokens.len() - 1);
-                                out_tokens.push(WrappedToken::from_with_line(Token::Equals, token.src_line));
+                                Tokenizer::coalesce_with_previous(&mut out_tokens, Token::Equals, &token);
                             }
                             Token::Symbol('!') => {
-                                out_tokens.truncate(out_tokens.len() - 1);
-                                out_tokens.push(WrappedToken::from_with_line(Token::NotEquals, token.src_line));
+                                Tokenizer::coalesce_with_previous(&mut out_tokens, Token::NotEquals, &token);
                             }
                             Token::Symbol('>') => {
-                                out_tokens.truncate(out_tokens.len() - 1);
-                                out_tokens.push(WrappedToken::from_with_line(Token::MoreThanOrEquals, token.src_line));
+                                Tokenizer::coalesce_with_previous(&mut out_tokens, Token::MoreThanOrEquals, &token);
                             }
                             Token::Symbol('<') => {
-                                out_tokens.truncate(out_tokens.len() - 1);
-                                out_tokens.push(WrappedToken::from_with_line(Token::LessThanOrEquals, token.src_line));
+                                Tokenizer::coalesce_with_previous(&mut out_tokens, Token::LessThanOrEquals, &token);
                             }
                             // math
                             Token::Symbol('+') => {
-                                out_tokens.truncate(out_tokens.len() - 1);
-                                out_tokens.push(WrappedToken::from_with_line(Token::PlusEquals, token.src_line));
+                                Tokenizer::coalesce_with_previous(&mut out_tokens, Token::PlusEquals, &token);
                             }
                             Token::Symbol('-') => {
-                                out_tokens.truncate(out_tokens.len() - 1);
-                                out_tokens.push(WrappedToken::from_with_line(Token::MinusEquals, token.src_line));
+                                Tokenizer::coalesce_with_previous(&mut out_tokens, Token::MinusEquals, &token);
+                            }
+                            Token::Symbol('*') => {
+                                Tokenizer::coalesce_with_previous(&mut out_tokens, Token::MultiplyEquals, &token);
+                            }
+                            Token::Symbol('/') => {
+                                Tokenizer::coalesce_with_previous(&mut out_tokens, Token::DivideEquals, &token);
+                            }
+                            Token::Symbol('%') => {
+                                Tokenizer::coalesce_with_previous(&mut out_tokens, Token::ModuloEquals, &token);
+                            }
+                            _ => {
+                                out_tokens.push(Tokenizer::unraw_token(token));
+                            }
+                        }
+                    }
+                    Token::Symbol('.') => {
+                        match &tokens[token_idx - 1].token { // get and replace previous token
+                            // range
+                            Token::Symbol('.') if out_tokens.last().is_some_and(|last| last.token == Token::LenAccess) => {
+                                Tokenizer::coalesce_with_previous(&mut out_tokens, Token::Range, &token);
+                            }
+                            _ => {
+                                out_tokens.push(Tokenizer::unraw_token(token));
+                            }
+                        }
+                    }
+                    Token::Symbol('&') => {
+                        match &tokens[token_idx - 1].token { // get and replace previous token
+                            // logical
+                            Token::Symbol('&') if out_tokens.last().is_some_and(|last| last.token == Token::Symbol('&')) => {
+                                Tokenizer::coalesce_with_previous(&mut out_tokens, Token::And, &token);
+                            }
+                            _ => {
+                                out_tokens.push(Tokenizer::unraw_token(token));
+                            }
+                        }
+                    }
+                    Token::Symbol('|') => {
+                        match &tokens[token_idx - 1].token { // get and replace previous token
+                            // logical, the expression parser splits it again between two indexes
+                            Token::Symbol('|') if out_tokens.last().is_some_and(|last| last.token == Token::ArrayAccess) => {
+                                Tokenizer::coalesce_with_previous(&mut out_tokens, Token::Or, &token);
                             }
                             _ => {
                                 out_tokens.push(Tokenizer::unraw_token(token));
@@ -210,8 +447,7 @@ impl Tokenizer {
                         match &tokens[token_idx - 1].token { // get and replace previous token
                             // subroutine call
                             Token::Symbol('-') => {
-                                out_tokens.truncate(out_tokens.len() - 1);
-                                out_tokens.push(WrappedToken::from_with_line(Token::SubroutineDirect, token.src_line));
+                                Tokenizer::coalesce_with_previous(&mut out_tokens, Token::SubroutineDirect, &token);
                             }
                             _ => {
                                 out_tokens.push(Tokenizer::unraw_token(token));
@@ -222,10 +458,10 @@ impl Tokenizer {
                         match value.as_str() {
                             "pop" => {
                                 match &tokens[token_idx - 1].token { // get and replace previous token
-                                    Token::Symbol('.') => {
+                                    // `.pop()` is left as an ordinary method call
+                                    Token::Symbol('.') if !Tokenizer::parens_follow(&tokens, token_idx) => {
                                         // pop
-                                        out_tokens.truncate(out_tokens.len() - 1);
-                                        out_tokens.push(WrappedToken::from_with_line(Token::PopAccess, token.src_line));
+                                        Tokenizer::coalesce_with_previous(&mut out_tokens, Token::PopAccess, &token);
                                     }
                                     _ => {
                                         // previous token was not '.' access, pop is a variable here
@@ -235,10 +471,10 @@ impl Tokenizer {
                             }
                             "popfront" => {
                                 match &tokens[token_idx - 1].token { // get and replace previous token
-                                    Token::Symbol('.') => {
+                                    // `.popfront()` is left as an ordinary method call
+                                    Token::Symbol('.') if !Tokenizer::parens_follow(&tokens, token_idx) => {
                                         // popfront
-                                        out_tokens.truncate(out_tokens.len() - 1);
-                                        out_tokens.push(WrappedToken::from_with_line(Token::PopFrontAccess, token.src_line));
+                                        Tokenizer::coalesce_with_previous(&mut out_tokens, Token::PopFrontAccess, &token);
                                     }
                                     _ => {
                                         // previous token was not '.' access, popfront is a variable here
@@ -267,7 +503,10 @@ impl Tokenizer {
                     scope_open_idxs.push(token_idx);
                 }
                 Token::ScopeClose => {
-                    scope_open_idxs.pop();
+                    scope_open_idxs.pop().ok_or_else(
+                        || Diagnostic::tokenizer("Unmatched '}'".to_string(), token.span)
+                            .with_label("no `{` to close")
+                    )?;
                 }
                 _ => {
                     // don't care
@@ -275,22 +514,81 @@ impl Tokenizer {
             }
         }
 
-        assert_eq!(scope_open_idxs.len(), 0);
-        return out_tokens;
-    }
+        if let Some(unclosed_idx) = scope_open_idxs.last() {
+            return Err(
+                Diagnostic::tokenizer("Unclosed '{'".to_string(), out_tokens[*unclosed_idx].span)
+                    .with_label("this scope is never closed")
+                    .with_help("add a matching `}`")
+            );
+        }
 
-    fn consume_integer(&mut self) -> WrappedToken {
-        let mut digit_str = String::new();
+        Ok(out_tokens)
+    }
 
+    fn consume_digits(&mut self, digit_str: &mut String) {
         while self.char_idx_in_bounds() && self.get_current_char().is_ascii_digit() {
             digit_str.push(self.get_current_char());
             self.char_idx += 1
         }
+    }
+
+    fn consume_number(&mut self, start: Position) -> Result<WrappedToken, Diagnostic> {
+        let mut digit_str = String::new();
+        let mut is_float = false;
+
+        self.consume_digits(&mut digit_str);
+
+        // a '.' is only a decimal point when a digit follows, otherwise it is the length accessor
+        if self.peek_char(0) == Some('.') && self.peek_char(1).is_some_and(|c| c.is_ascii_digit()) {
+            digit_str.push('.');
+            self.char_idx += 1;
+            self.consume_digits(&mut digit_str);
+            is_float = true;
+        }
+
+        // exponent, only taken when digits follow the `e`
+        if matches!(self.peek_char(0), Some('e') | Some('E')) {
+            let sign_len = if matches!(self.peek_char(1), Some('+') | Some('-')) { 1 } else { 0 };
+
+            if self.peek_char(1 + sign_len).is_some_and(|c| c.is_ascii_digit()) {
+                digit_str.push('e');
+                self.char_idx += 1;
+
+                if sign_len == 1 {
+                    digit_str.push(self.get_current_char());
+                    self.char_idx += 1;
+                }
+
+                self.consume_digits(&mut digit_str);
+                is_float = true;
+            }
+        }
+
+        if is_float {
+            // the shape is already validated, only the size can be wrong
+            let value = digit_str.parse::<f64>().unwrap();
+
+            if !value.is_finite() {
+                return Err(
+                    Diagnostic::tokenizer(format!("Float literal {} is too large", digit_str), self.span_from(start))
+                        .with_label(&format!("larger than the largest float, {:e}", f64::MAX))
+                );
+            }
+
+            return Ok(WrappedToken::from_with_span(Token::FloatLiteral(value), self.span_from(start)));
+        }
 
-        WrappedToken::from_with_line(Token::IntegerLiteral(u32::from_str_radix(&digit_str, 10).unwrap()), self.line_idx + 1)
+        // 2^63 only fits negated, it is kept as i64::MIN and the expression parser checks for the minus
+        match digit_str.parse::<u64>().ok().filter(|value| *value <= i64::MIN.unsigned_abs()) {
+            Some(value) => Ok(WrappedToken::from_with_span(Token::IntegerLiteral(value as i64), self.span_from(start))),
+            None => Err(
+                Diagnostic::tokenizer(format!("Integer literal {} is too large", digit_str), self.span_from(start))
+                    .with_label(&format!("does not fit in {} bits", i64::BITS))
+            ),
+        }
     }
 
-    fn consume_identifier(&mut self) -> WrappedToken {
+    fn consume_identifier(&mut self, start: Position) -> WrappedToken {
         let mut identifier_str = String::new();
 
         // identifiers may contain a number, only the start needs to be a letter
@@ -299,35 +597,185 @@ impl Tokenizer {
             self.char_idx += 1
         }
 
-        WrappedToken::from_with_line(Token::RawIdentifier(identifier_str), self.line_idx + 1)
+        WrappedToken::from_with_span(Token::RawIdentifier(identifier_str), self.span_from(start))
+    }
+
+    fn triple_quote_follows(&self) -> bool {
+        (0..3).all(|offset| self.peek_char(offset) == Some('"'))
     }
 
-    fn consume_string_literal(&mut self) -> WrappedToken {
+    // `"..."` or `"""..."""`, which may span lines and keeps the line breaks as written
+    fn consume_string_literal(&mut self, start: Position, raw: bool) -> Result<WrappedToken, Diagnostic> {
         let mut literal_str = String::new();
-        self.char_idx += 1; // go into bounds of string
+        let mut parts: Vec<StringPart> = vec![];
+        let quote_len = if self.triple_quote_follows() { 3 } else { 1 };
+        self.char_idx += quote_len; // go into bounds of string
 
-        while self.char_idx_in_bounds() && self.get_current_char() != '"' {
-            literal_str.push(self.get_current_char());
-            self.char_idx += 1
+        loop {
+            if !self.char_idx_in_bounds() {
+                if quote_len == 3 && self.line_idx + 1 < self.lines.len() {
+                    literal_str.push('\n');
+                    self.line_idx += 1;
+                    self.char_idx = 0;
+                    continue;
+                }
+
+                let help = if quote_len == 3 {
+                    "multi-line strings end with `\"\"\"`"
+                } else {
+                    "strings end with `\"` on the same line, use `\"\"\"` for one spanning lines"
+                };
+
+                return Err(
+                    Diagnostic::tokenizer("Unterminated string".to_string(), self.span_from(start))
+                        .with_label("string starts here and is never closed")
+                        .with_help(help)
+                );
+            }
+
+            let this_char = self.get_current_char();
+
+            if this_char == '"' && (quote_len == 1 || self.triple_quote_follows()) {
+                break;
+            } else if this_char == '\\' && !raw {
+                literal_str.push(self.consume_escape()?);
+            } else if this_char == '{' && !raw {
+                parts.push(StringPart::Literal(std::mem::take(&mut literal_str)));
+                parts.push(StringPart::Expression(self.consume_interpolation()?));
+            } else {
+                literal_str.push(this_char);
+                self.char_idx += 1
+            }
+        }
+
+        self.char_idx += quote_len; // leave string bounds
+
+        if parts.is_empty() {
+            return Ok(WrappedToken::from_with_span(Token::StringLiteral(literal_str), self.span_from(start)));
+        }
+
+        parts.push(StringPart::Literal(literal_str));
+        Ok(WrappedToken::from_with_span(Token::InterpolatedString(parts), self.span_from(start)))
+    }
+
+    // the tokens of a `{expression}` inside a string, strings nested in it are tokenized as usual
+    fn consume_interpolation(&mut self) -> Result<Vec<WrappedToken>, Diagnostic> {
+        let start = self.position();
+        let mut tokens: Vec<WrappedToken> = vec![];
+        self.char_idx += 1; // skip {
+
+        loop {
+            // a quote with no partner on the line ends the outer string, not a nested one
+            let closes_outer = self.char_idx_in_bounds() && self.get_current_char() == '"'
+                && !self.get_current_line().chars().skip(self.char_idx + 1).any(|c| c == '"');
+
+            if !self.char_idx_in_bounds() || closes_outer {
+                return Err(
+                    Diagnostic::tokenizer("Unclosed `{` in string".to_string(), self.span_from(start))
+                        .with_label("interpolation starts here")
+                        .with_help("close it with `}` on the same line, or write `\\{` for a literal brace")
+                );
+            }
+
+            if self.get_current_char() == '}' {
+                self.char_idx += 1;
+                break;
+            }
+
+            tokens.push(self.next_token()?);
+        }
+
+        let tokens = Tokenizer::post_process(tokens)?;
+
+        if tokens.is_empty() {
+            return Err(
+                Diagnostic::tokenizer("Empty interpolation in string".to_string(), self.span_from(start))
+                    .with_label("expected an expression between the braces")
+                    .with_help("write `\\{` for a literal brace")
+            );
+        }
+
+        Ok(tokens)
+    }
+
+    // decodes the escape at the current `\\`, leaving char_idx just past it
+    fn consume_escape(&mut self) -> Result<char, Diagnostic> {
+        let start = self.position();
+        self.char_idx += 1; // skip \\
+
+        let escaped = match self.peek_char(0) {
+            Some(escaped) => escaped,
+            None => return Err(
+                Diagnostic::tokenizer("Incomplete escape sequence".to_string(), self.span_from(start))
+                    .with_label("escape at the end of the line")
+            ),
+        };
+        self.char_idx += 1;
+
+        match escaped {
+            'n' => Ok('\n'),
+            't' => Ok('\t'),
+            'r' => Ok('\r'),
+            '0' => Ok('\0'),
+            '\\' => Ok('\\'),
+            '"' => Ok('"'),
+            '{' => Ok('{'),
+            '}' => Ok('}'),
+            'u' => self.consume_unicode_escape(start),
+            _ => Err(
+                Diagnostic::tokenizer(format!("Unknown escape sequence `\\{}`", escaped), self.span_from(start))
+                    .with_label("not a valid escape")
+                    .with_help("valid escapes are `\\n`, `\\t`, `\\r`, `\\0`, `\\\\`, `\\\"`, `\\{`, `\\}` and `\\u{...}`")
+            ),
+        }
+    }
+
+    // `\\u{1F44D}`, the `\\u` is already consumed
+    fn consume_unicode_escape(&mut self, start: Position) -> Result<char, Diagnostic> {
+        let mut hex_str = String::new();
+
+        if self.peek_char(0) == Some('{') {
+            self.char_idx += 1;
+
+            while self.peek_char(0).is_some_and(|c| c.is_ascii_hexdigit()) {
+                hex_str.push(self.get_current_char());
+                self.char_idx += 1;
+            }
+
+            if self.peek_char(0) == Some('}') && !hex_str.is_empty() && hex_str.len() <= 6 {
+                self.char_idx += 1;
+
+                if let Some(char_val) = u32::from_str_radix(&hex_str, 16).ok().and_then(char::from_u32) {
+                    return Ok(char_val);
+                }
+
+                return Err(
+                    Diagnostic::tokenizer(format!("Invalid unicode escape, {} is not a char", hex_str), self.span_from(start))
+                        .with_label("not a unicode scalar value")
+                );
+            }
         }
 
-        self.char_idx += 1; // leave string bounds
-        WrappedToken::from_with_line(Token::StringLiteral(literal_str), self.line_idx + 1)
+        Err(
+            Diagnostic::tokenizer("Malformed unicode escape".to_string(), self.span_from(start))
+                .with_label("expected 1 to 6 hex digits in braces")
+                .with_help("write it like `\\u{e9}`")
+        )
     }
 
-    fn consume_whitespace(&mut self) -> WrappedToken {
+    fn consume_whitespace(&mut self, start: Position) -> WrappedToken {
         while self.char_idx_in_bounds() && self.get_current_char().is_ascii_whitespace() {
             self.char_idx += 1
         }
 
-        WrappedToken::from_with_line(Token::Whitespace, self.line_idx + 1)
+        WrappedToken::from_with_span(Token::Whitespace, self.span_from(start))
     }
 
-    fn consume_comment(&mut self) -> WrappedToken {
+    fn consume_comment(&mut self, start: Position) -> WrappedToken {
         while self.char_idx_in_bounds() {
             self.char_idx += 1
         }
 
-        WrappedToken::from(Token::Comment)
+        WrappedToken::from_with_span(Token::Comment, self.span_from(start))
     }
 }
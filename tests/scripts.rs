@@ -0,0 +1,69 @@
+use std::fs::{self, File};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+// runs every tests/*.homu and compares stdout with the .out next to it,
+// a .err instead means the script must fail with exactly that diagnostic, a .in is fed to stdin
+#[test]
+fn scripts_match_expected_output() {
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    let mut scripts: Vec<String> = fs::read_dir(Path::new(manifest_dir).join("tests"))
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .filter(|name| name.ends_with(".homu"))
+        .collect();
+    let mut failures: Vec<String> = vec![];
+    scripts.sort();
+
+    assert!(!scripts.is_empty(), "no scripts found in tests/");
+
+    for script in &scripts {
+        let stem = script.trim_end_matches(".homu");
+        let stdin = match File::open(format!("{}/tests/{}.in", manifest_dir, stem)) {
+            Ok(file) => Stdio::from(file),
+            Err(_) => Stdio::null(),
+        };
+        // relative, so diagnostics show the same path on every machine
+        let output = Command::new(env!("CARGO_BIN_EXE_homulang"))
+            .arg(format!("tests/{}", script))
+            .current_dir(manifest_dir)
+            .stdin(stdin)
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let expected_out = fs::read_to_string(format!("{}/tests/{}.out", manifest_dir, stem));
+        let expected_err = fs::read_to_string(format!("{}/tests/{}.err", manifest_dir, stem));
+
+        match (expected_out, expected_err) {
+            (Ok(expected), _) if !output.status.success() => {
+                failures.push(format!("{} failed, expected:\n{}\nstderr:\n{}", script, expected, stderr));
+            }
+            (Ok(expected), _) if stdout != expected => {
+                failures.push(format!("{} printed:\n{}\nexpected:\n{}", script, stdout, expected));
+            }
+            (_, Ok(_)) if output.status.success() => {
+                failures.push(format!("{} succeeded but should have failed", script));
+            }
+            (_, Ok(expected)) if stderr != expected => {
+                failures.push(format!("{} reported:\n{}\nexpected:\n{}", script, stderr, expected));
+            }
+            (Err(_), Err(_)) => failures.push(format!("{} has no .out or .err file", script)),
+            _ => {}
+        }
+    }
+
+    assert!(failures.is_empty(), "{}", failures.join("\n\n"));
+}
+
+#[test]
+fn missing_file_is_reported() {
+    let output = Command::new(env!("CARGO_BIN_EXE_homulang")).arg("tests/does_not_exist.homu")
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr.starts_with("error: cannot read tests/does_not_exist.homu: "), "{}", stderr);
+}